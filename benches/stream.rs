@@ -0,0 +1,78 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nom::error::Error;
+use syslog_parse::parser::StreamParser;
+use syslog_parse::stream::MsgStream;
+
+fn input() -> Vec<u8> {
+    (0..1000)
+        .map(|i| {
+            format!(
+                "<46>1 2023-09-13T11:15:47.{:06}Z localhost dygen 48747 {} [meta@1 seq=\"{}\"] hello world",
+                i, i, i
+            )
+        })
+        .collect::<String>()
+        .into_bytes()
+}
+
+fn msg_stream(input: &[u8], chunk: usize) -> usize {
+    let mut stream = MsgStream::default();
+    let mut count = 0;
+    for chunk in input.chunks(chunk) {
+        count += stream
+            .multi_parse_messages(chunk.to_vec())
+            .into_iter()
+            .filter(Result::is_ok)
+            .count();
+    }
+    count + stream.flush_messages().len()
+}
+
+fn stream_parser(input: &[u8], chunk: usize) -> usize {
+    let mut parser = StreamParser::new();
+    let mut count = 0;
+    for chunk in input.chunks(chunk) {
+        parser.feed(chunk);
+        while let Some(frame) = parser.next_frame() {
+            count += frame.into_message().is_ok() as usize;
+        }
+    }
+    parser.finish();
+    while let Some(frame) = parser.next_frame() {
+        count += frame.into_message().is_ok() as usize;
+    }
+    count
+}
+
+fn bench_stream(c: &mut Criterion) {
+    let input = input();
+    assert_eq!(msg_stream(&input, 7), 1000);
+    assert_eq!(stream_parser(&input, 7), 1000);
+
+    let mut group = c.benchmark_group("split");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    for chunk in [7, 64, 4096] {
+        group.bench_with_input(BenchmarkId::new("MsgStream", chunk), &chunk, |b, chunk| {
+            b.iter(|| msg_stream(&input, *chunk))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("StreamParser", chunk),
+            &chunk,
+            |b, chunk| b.iter(|| stream_parser(&input, *chunk)),
+        );
+    }
+    group.finish();
+
+    // the string API, as used before parsed messages were available
+    c.bench_function("MsgStream::multi_parse/4096", |b| {
+        b.iter(|| {
+            let mut stream = MsgStream::default();
+            for chunk in input.chunks(4096) {
+                stream.multi_parse::<Error<&str>>(chunk.to_vec()).unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, bench_stream);
+criterion_main!(benches);
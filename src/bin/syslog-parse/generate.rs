@@ -0,0 +1,277 @@
+use clap::{ArgGroup, Args};
+use std::io::{self, BufWriter, Write};
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use syslog_parse::client::{Client, Destination};
+use syslog_parse::error::GeneratorError;
+use syslog_parse::framing::Framing;
+use syslog_parse::generator::{Generated, Generator};
+use syslog_parse::message::{Facility, Severity};
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("target")))]
+pub struct GenerateArgs {
+    /// Stop after this many messages instead of running until killed
+    #[arg(short = 'n', long)]
+    count: Option<u64>,
+
+    /// Messages per second, as fast as possible if not given
+    #[arg(long, value_parser = rate)]
+    rate: Option<f64>,
+
+    /// Seed for reproducible output, printed on stderr when not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Share of RFC3164 messages, 0 to 1
+    #[arg(long, value_parser = ratio, default_value = "0")]
+    rfc3164: f64,
+
+    /// Weighted facilities, e.g. user:4,auth
+    #[arg(long, value_delimiter = ',', value_parser = weighted::<Facility>)]
+    facility: Vec<(Facility, u32)>,
+
+    /// Weighted severities, e.g. info:10,err:1
+    #[arg(long, value_delimiter = ',', value_parser = weighted::<Severity>)]
+    severity: Vec<(Severity, u32)>,
+
+    /// Weighted hostnames, e.g. web1:3,web2
+    #[arg(long, value_delimiter = ',', value_parser = weighted::<String>)]
+    host: Vec<(String, u32)>,
+
+    /// Weighted app names, e.g. sshd,cron:2
+    #[arg(long, value_delimiter = ',', value_parser = weighted::<String>)]
+    app: Vec<(String, u32)>,
+
+    /// Share of RFC5424 messages with structured data, 0 to 1
+    #[arg(long, value_parser = ratio, default_value = "0.5")]
+    sd: f64,
+
+    /// MSG length in bytes, e.g. 100 or 10-80
+    #[arg(long, value_parser = size_range, default_value = "10-80")]
+    body_size: (usize, usize),
+
+    /// Share of malformed messages, 0 to 1
+    #[arg(long, value_parser = ratio, default_value = "0")]
+    malformed: f64,
+
+    /// Send to this UDP address instead of stdout
+    #[arg(long, group = "target")]
+    udp: Option<SocketAddr>,
+
+    /// Send to this TCP address instead of stdout
+    #[arg(long, group = "target")]
+    tcp: Option<SocketAddr>,
+
+    /// Send to this Unix datagram socket instead of stdout
+    #[cfg(unix)]
+    #[arg(long, group = "target")]
+    unix: Option<PathBuf>,
+
+    /// Framing on stdout and TCP, lf and octet-counting by default
+    #[arg(long)]
+    framing: Option<Framing>,
+}
+
+/// `name` or `name:weight`.
+fn weighted<T: FromStr>(s: &str) -> Result<(T, u32), String>
+where
+    T::Err: ToString,
+{
+    let (name, weight) = match s.rsplit_once(':') {
+        Some((name, weight)) => {
+            let weight = weight
+                .parse()
+                .map_err(|_| format!("invalid weight {:?}", weight))?;
+            (name, weight)
+        }
+        None => (s, 1),
+    };
+    Ok((name.parse().map_err(|e: T::Err| e.to_string())?, weight))
+}
+
+/// Slowest rate: one message a day, so the schedule stays in range.
+const MIN_RATE: f64 = 1.0 / 86400.0;
+
+fn rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate >= MIN_RATE => Ok(rate),
+        _ => Err(format!(
+            "{:?} is not a rate of at least one message a day ({})",
+            s, MIN_RATE
+        )),
+    }
+}
+
+fn ratio(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!("{:?} is not a number from 0 to 1", s)),
+    }
+}
+
+/// `len` or `min-max`.
+fn size_range(s: &str) -> Result<(usize, usize), String> {
+    let invalid = |_| format!("invalid size {:?}", s);
+    match s.split_once('-') {
+        Some((min, max)) => {
+            let (min, max) = (min.parse().map_err(invalid)?, max.parse().map_err(invalid)?);
+            if min > max {
+                return Err(format!("invalid size {:?}", s));
+            }
+            Ok((min, max))
+        }
+        None => s.parse().map(|len| (len, len)).map_err(invalid),
+    }
+}
+
+/// The weights given, or the flag of the first one `Generator` rejects.
+fn set_weights(
+    generator: &mut Generator,
+    args: &GenerateArgs,
+) -> Result<(), (&'static str, GeneratorError)> {
+    if !args.facility.is_empty() {
+        generator
+            .set_facilities(args.facility.clone())
+            .map_err(|e| ("--facility", e))?;
+    }
+    if !args.severity.is_empty() {
+        generator
+            .set_severities(args.severity.clone())
+            .map_err(|e| ("--severity", e))?;
+    }
+    if !args.host.is_empty() {
+        generator
+            .set_hosts(args.host.clone())
+            .map_err(|e| ("--host", e))?;
+    }
+    if !args.app.is_empty() {
+        generator
+            .set_apps(args.app.clone())
+            .map_err(|e| ("--app", e))?;
+    }
+    Ok(())
+}
+
+enum Output {
+    Stdout(BufWriter<io::Stdout>, Framing),
+    Client(Box<Client>),
+}
+
+impl Output {
+    fn new(args: &GenerateArgs) -> Self {
+        let framing = args.framing.unwrap_or(Framing::OctetCounting);
+        let destination = if let Some(addr) = args.udp {
+            Destination::Udp(addr)
+        } else if let Some(addr) = args.tcp {
+            Destination::Tcp(addr, framing)
+        } else {
+            #[cfg(unix)]
+            if let Some(path) = &args.unix {
+                return Output::Client(Box::new(Client::new(Destination::UnixDatagram(
+                    path.clone(),
+                ))));
+            }
+            let framing = args.framing.unwrap_or(Framing::LF);
+            return Output::Stdout(BufWriter::new(io::stdout()), framing);
+        };
+        Output::Client(Box::new(Client::new(destination)))
+    }
+
+    /// Write a message. Client errors are left to its reconnection logic
+    /// and only counted.
+    fn send(&mut self, generated: &Generated) -> io::Result<()> {
+        match self {
+            Output::Stdout(out, framing) => framing.encode(&generated.to_string(), out),
+            Output::Client(client) => {
+                let _ = match generated {
+                    Generated::Valid(msg) => client.send(msg),
+                    Generated::Malformed(text) => client.send_str(text),
+                };
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(out, _) => out.flush(),
+            Output::Client(client) => {
+                let result = client.flush();
+                let stats = client.stats();
+                eprintln!(
+                    "{} sent, {} dropped, {} rejected, {} failures",
+                    stats.sent, stats.dropped, stats.rejected, stats.failures
+                );
+                result
+            }
+        }
+    }
+}
+
+pub fn run(args: GenerateArgs) -> io::Result<ExitCode> {
+    let seed = args.seed.unwrap_or_else(|| {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        eprintln!("seed {}", seed);
+        seed
+    });
+    let mut generator = Generator::new(seed);
+    generator.set_rfc3164_ratio(args.rfc3164);
+    generator.set_sd_ratio(args.sd);
+    generator.set_body_size(args.body_size.0, args.body_size.1);
+    generator.set_malformed_ratio(args.malformed);
+    if let Err((flag, e)) = set_weights(&mut generator, &args) {
+        eprintln!("syslog-parse: {}: {}", flag, e);
+        return Ok(ExitCode::from(2));
+    }
+
+    let mut output = Output::new(&args);
+    let started = Instant::now();
+    let mut sent = 0u64;
+    while args.count.is_none_or(|count| sent < count) {
+        if let Some(rate) = args.rate {
+            let due = started + Duration::from_secs_f64(sent as f64 / rate);
+            let now = Instant::now();
+            if due > now {
+                if let Output::Stdout(out, _) = &mut output {
+                    out.flush()?;
+                }
+                thread::sleep(due - now);
+            }
+        }
+        output.send(&generator.generate())?;
+        sent += 1;
+    }
+    output.finish()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_parsers() {
+        assert_eq!(weighted("auth:3"), Ok((Facility::Auth, 3)));
+        assert_eq!(weighted("err"), Ok((Severity::Err, 1)));
+        assert_eq!(weighted("web1:2"), Ok(("web1".to_string(), 2)));
+        assert!(weighted::<Facility>("auth:x").is_err());
+        assert!(weighted::<Facility>("nope").is_err());
+        assert_eq!(size_range("10-80"), Ok((10, 80)));
+        assert_eq!(size_range("100"), Ok((100, 100)));
+        assert!(size_range("80-10").is_err());
+        assert_eq!(rate("0.5"), Ok(0.5));
+        for invalid in ["0", "-1", "1e-300", "NaN", "inf", "x"] {
+            assert!(rate(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(ratio("0.25"), Ok(0.25));
+        assert!(ratio("1.5").is_err());
+    }
+}
@@ -0,0 +1,371 @@
+use clap::{Args, ValueEnum};
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use syslog_parse::filter::{self, Predicate};
+use syslog_parse::framing::Framing;
+use syslog_parse::message::{Facility, Message, Severity};
+use syslog_parse::selector::Selector;
+use syslog_parse::server::tcp::TcpServer;
+use syslog_parse::server::udp::UdpServer;
+#[cfg(unix)]
+use syslog_parse::server::unix::UnixDatagramServer;
+use syslog_parse::server::Received;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub enum Color {
+    /// Only when stdout is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Args)]
+pub struct ListenArgs {
+    /// UDP address to receive on, e.g. 0.0.0.0:514
+    #[arg(long)]
+    udp: Vec<SocketAddr>,
+
+    /// TCP address to accept connections on, e.g. 0.0.0.0:601
+    #[arg(long)]
+    tcp: Vec<SocketAddr>,
+
+    /// Unix datagram socket to create, e.g. /dev/log
+    #[cfg(unix)]
+    #[arg(long)]
+    unix: Vec<PathBuf>,
+
+    /// How TCP streams are delimited: heuristic, octet-counting, lf or nul
+    #[arg(long, default_value = "heuristic")]
+    framing: Framing,
+
+    /// Comma separated facilities to show, e.g. auth,authpriv
+    #[arg(long, value_delimiter = ',')]
+    facility: Vec<Facility>,
+
+    /// Show only this severity and more severe ones, e.g. warning
+    #[arg(long)]
+    severity: Option<Severity>,
+
+    /// Comma separated hostnames to show
+    #[arg(long, value_delimiter = ',')]
+    host: Vec<String>,
+
+    /// Comma separated app names to show
+    #[arg(long, value_delimiter = ',')]
+    app: Vec<String>,
+
+    /// syslog.conf selector, e.g. 'auth,authpriv.*;kern.warning'
+    ///
+    /// As in syslog.conf, security means auth (4) here, while --facility
+    /// takes it as the security facility (13).
+    #[arg(long)]
+    selector: Option<Selector>,
+
+    /// Filter expression, e.g. 'severity <= warning and app == "sshd"'
+    #[arg(long, value_parser = crate::filter_expr)]
+    filter: Option<filter::Filter>,
+
+    /// Append every received message, unfiltered, to this file with
+    /// octet-counting framing, for `replay`
+    #[arg(long)]
+    capture: Option<PathBuf>,
+
+    /// Stop after printing this many messages
+    #[arg(short = 'n', long)]
+    count: Option<u64>,
+
+    #[arg(long, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+}
+
+/// Which messages are printed. Empty lists match everything.
+#[derive(Debug, Default)]
+pub struct Filter {
+    facility: Vec<Facility>,
+    severity: Option<Severity>,
+    host: Vec<String>,
+    app: Vec<String>,
+    selector: Option<Selector>,
+    expr: Option<filter::Filter>,
+}
+
+impl Filter {
+    pub fn matches(&self, msg: &Message) -> bool {
+        let h = &msg.header;
+        let any = |names: &[String], value: &Option<String>| {
+            names.is_empty() || value.as_ref().is_some_and(|v| names.contains(v))
+        };
+        (self.facility.is_empty() || self.facility.contains(&h.facility))
+            && self.severity.is_none_or(|max| h.severity <= max)
+            && any(&self.host, &h.hostname)
+            && any(&self.app, &h.appname)
+            && self.selector.is_none_or(|selector| selector.matches(msg))
+            && self.expr.as_ref().is_none_or(|expr| expr.matches(msg))
+    }
+
+    /// Unparsable messages are only shown without filters, since none of
+    /// the fields can be checked.
+    pub fn is_empty(&self) -> bool {
+        self.facility.is_empty()
+            && self.severity.is_none()
+            && self.host.is_empty()
+            && self.app.is_empty()
+            && self.selector.is_none()
+            && self.expr.is_none()
+    }
+}
+
+/// ANSI SGR parameters for a severity.
+fn color(severity: Severity) -> Option<&'static str> {
+    match severity {
+        Severity::Emerg | Severity::Alert | Severity::Crit => Some("1;31"),
+        Severity::Err => Some("31"),
+        Severity::Warning => Some("33"),
+        Severity::Notice => Some("36"),
+        Severity::Info => None,
+        Severity::Debug => Some("2"),
+    }
+}
+
+/// One line per received message, colorized by severity.
+pub fn format(received: &Received, colored: bool) -> String {
+    let time = received.received_at.format("%H:%M:%S%.3f");
+    let (line, sgr) = match &received.message {
+        Ok(msg) => {
+            let h = &msg.header;
+            let tag = match (&h.appname, &h.procid) {
+                (Some(app), Some(pid)) => format!("{}[{}]", app, pid),
+                (Some(app), None) => app.clone(),
+                (None, _) => "-".to_string(),
+            };
+            let line = format!(
+                "{} {} {}.{} {} {}: {}",
+                time,
+                received.peer,
+                h.facility,
+                h.severity,
+                h.hostname.as_deref().unwrap_or("-"),
+                tag,
+                msg.msg.trim_end_matches(['\r', '\n'])
+            );
+            (line, color(h.severity))
+        }
+        Err((raw, kind)) => (
+            format!("{} {} invalid: {}: {:?}", time, received.peer, kind, raw),
+            Some("35"),
+        ),
+    };
+    match sgr {
+        Some(sgr) if colored => format!("\x1b[{}m{}\x1b[0m", sgr, line),
+        _ => line,
+    }
+}
+
+/// Write a message to the capture file, as it was received.
+pub fn capture<W: Write>(received: &Received, out: &mut W) -> io::Result<()> {
+    Framing::OctetCounting.encode_bytes(&received.raw, out)
+}
+
+/// Run `serve` on its own thread, reporting a failure on stderr.
+fn spawn<F>(name: String, serve: F)
+where
+    F: FnOnce() -> io::Result<()> + Send + 'static,
+{
+    thread::spawn(move || {
+        if let Err(e) = serve() {
+            eprintln!("{}: {}", name, e);
+        }
+    });
+}
+
+/// Bind every receiver before starting any, so a bad address fails early.
+fn start(args: &ListenArgs, tx: &Sender<Received>) -> io::Result<()> {
+    let mut udp = Vec::new();
+    for addr in &args.udp {
+        udp.push(UdpServer::bind(addr).map_err(|e| context(e, addr))?);
+    }
+    let mut tcp = Vec::new();
+    for addr in &args.tcp {
+        let mut server = TcpServer::bind(addr).map_err(|e| context(e, addr))?;
+        server.set_framing(args.framing);
+        tcp.push(server);
+    }
+    #[cfg(unix)]
+    let mut unix = Vec::new();
+    #[cfg(unix)]
+    for path in &args.unix {
+        let server = UnixDatagramServer::bind(path).map_err(|e| context(e, path.display()))?;
+        unix.push((path.clone(), server));
+    }
+
+    for mut server in udp {
+        let tx = tx.clone();
+        spawn(format!("udp {}", server.local_addr()?), move || {
+            server.run(tx)
+        });
+    }
+    for server in tcp {
+        let tx = tx.clone();
+        spawn(format!("tcp {}", server.local_addr()?), move || {
+            server.run(tx)
+        });
+    }
+    #[cfg(unix)]
+    for (path, mut server) in unix {
+        let tx = tx.clone();
+        spawn(format!("unix {}", path.display()), move || server.run(tx));
+    }
+    Ok(())
+}
+
+fn context(e: io::Error, addr: impl std::fmt::Display) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", addr, e))
+}
+
+pub fn run(args: ListenArgs) -> io::Result<ExitCode> {
+    #[cfg(unix)]
+    let none = args.udp.is_empty() && args.tcp.is_empty() && args.unix.is_empty();
+    #[cfg(not(unix))]
+    let none = args.udp.is_empty() && args.tcp.is_empty();
+    if none {
+        eprintln!("syslog-parse: nothing to listen on, give --udp, --tcp or --unix");
+        return Ok(ExitCode::from(2));
+    }
+    let mut capture_file = match &args.capture {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path);
+            Some(BufWriter::new(
+                file.map_err(|e| context(e, path.display()))?,
+            ))
+        }
+        None => None,
+    };
+
+    let (tx, rx) = mpsc::channel();
+    start(&args, &tx)?;
+    drop(tx);
+
+    let colored = match args.color {
+        Color::Auto => io::stdout().is_terminal(),
+        Color::Always => true,
+        Color::Never => false,
+    };
+    let filter = Filter {
+        facility: args.facility,
+        severity: args.severity,
+        host: args.host,
+        app: args.app,
+        selector: args.selector,
+        expr: args.filter,
+    };
+    let mut out = io::stdout().lock();
+    let mut printed = 0;
+    for received in rx {
+        if let Some(file) = &mut capture_file {
+            capture(&received, file)?;
+            file.flush()?;
+        }
+        let shown = match &received.message {
+            Ok(msg) => filter.matches(msg),
+            Err(_) => filter.is_empty(),
+        };
+        if !shown {
+            continue;
+        }
+        writeln!(out, "{}", format(&received, colored))?;
+        printed += 1;
+        if args.count.is_some_and(|count| printed >= count) {
+            break;
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+    use syslog_parse::error::ErrorKind;
+    use syslog_parse::server::Peer;
+
+    fn received(message: &str) -> Received {
+        Received {
+            peer: Peer::Inet("10.0.0.7:5140".parse().unwrap()),
+            received_at: Local.with_ymd_and_hms(2024, 3, 1, 12, 30, 5).unwrap(),
+            message: Message::parse(message).map_err(|kind| (message.to_string(), kind)),
+            raw: message.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let msg = Message::parse("<38>Mar  1 12:30:05 web1 sshd[42]: Accepted").unwrap();
+        assert!(Filter::default().matches(&msg));
+
+        let filter = Filter {
+            facility: vec![Facility::Auth, Facility::Authpriv],
+            severity: Some(Severity::Info),
+            host: vec!["web1".to_string()],
+            app: vec!["sshd".to_string()],
+            selector: Some("auth.*;auth.!=debug".parse().unwrap()),
+            expr: Some("procid == 42".parse().unwrap()),
+        };
+        assert!(filter.matches(&msg));
+        let filter = Filter {
+            severity: Some(Severity::Warning),
+            ..Filter::default()
+        };
+        assert!(!filter.matches(&msg));
+        let filter = Filter {
+            app: vec!["cron".to_string()],
+            ..Filter::default()
+        };
+        assert!(!filter.matches(&msg));
+    }
+
+    #[test]
+    fn test_format() {
+        let ok = received("<38>Mar  1 12:30:05 web1 sshd[42]: Accepted");
+        assert_eq!(
+            format(&ok, false),
+            "12:30:05.000 10.0.0.7:5140 auth.info web1 sshd[42]: Accepted"
+        );
+        // info is printed without color
+        assert_eq!(format(&ok, true), format(&ok, false));
+
+        let warning = received("<4>1 2024-03-01T12:30:05Z - kernel - - - low memory");
+        assert_eq!(
+            format(&warning, true),
+            "\x1b[33m12:30:05.000 10.0.0.7:5140 kern.warning - kernel: low memory\x1b[0m"
+        );
+
+        let invalid = received("garbage");
+        assert_eq!(
+            format(&invalid, false),
+            format!(
+                "12:30:05.000 10.0.0.7:5140 invalid: {}: \"garbage\"",
+                ErrorKind::Header
+            )
+        );
+    }
+
+    #[test]
+    fn test_capture() {
+        let mut out = Vec::new();
+        capture(&received("<38>Mar  1 12:30:05 web1 sshd[42]: hi"), &mut out).unwrap();
+        capture(&received("garbage"), &mut out).unwrap();
+        // the bytes received, not the message as decoded
+        let mut lossy = received("<38>Mar  1 12:30:05 web1 sshd[42]: \u{fffd}");
+        lossy.raw = b"<38>Mar  1 12:30:05 web1 sshd[42]: \xff".to_vec();
+        capture(&lossy, &mut out).unwrap();
+        assert_eq!(
+            out,
+            b"37 <38>Mar  1 12:30:05 web1 sshd[42]: hi7 garbage36 <38>Mar  1 12:30:05 web1 sshd[42]: \xff"
+        );
+    }
+}
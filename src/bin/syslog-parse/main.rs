@@ -0,0 +1,223 @@
+mod generate;
+mod listen;
+mod output;
+mod replay;
+mod validate;
+
+use clap::{Args, Parser, Subcommand};
+use output::{Format, Output};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use syslog_parse::field::Field;
+use syslog_parse::filter::{Filter, Predicate};
+use syslog_parse::framing::Framing;
+use syslog_parse::message::Message;
+use syslog_parse::reader::Messages;
+use syslog_parse::template::Template;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "syslog-parse",
+    version,
+    about = "Parse and inspect syslog messages"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print messages as JSON lines, CSV or a table
+    Parse(ParseArgs),
+    /// Check messages against RFC5424 and RFC3164
+    Validate(validate::ValidateArgs),
+    /// Receive messages over the network and print them as they arrive
+    Listen(listen::ListenArgs),
+    /// Re-send captured messages with their original timing
+    Replay(replay::ReplayArgs),
+    /// Produce synthetic messages at a given rate
+    Generate(generate::GenerateArgs),
+}
+
+#[derive(Debug, Args)]
+struct InputArgs {
+    /// Files to read, `-` for stdin (the default)
+    files: Vec<PathBuf>,
+
+    /// How messages are delimited: heuristic, octet-counting, lf or nul
+    #[arg(long, default_value = "heuristic")]
+    framing: Framing,
+}
+
+#[derive(Debug, Args)]
+struct ParseArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Comma separated fields to print, e.g. timestamp,hostname,msg
+    #[arg(long, value_delimiter = ',')]
+    fields: Vec<Field>,
+
+    /// Print only messages matching this expression, e.g. 'severity <= err'
+    #[arg(long, value_parser = filter_expr)]
+    filter: Option<Filter>,
+
+    /// Print each message with an rsyslog style template instead, e.g.
+    /// '%HOSTNAME% %syslogtag% %msg%\n'
+    #[arg(long, value_parser = template, conflicts_with_all = ["format", "fields"])]
+    template: Option<Template>,
+}
+
+impl InputArgs {
+    /// Each input with a name for error reports.
+    fn sources(&self) -> Vec<(String, Option<&PathBuf>)> {
+        if self.files.is_empty() {
+            return vec![("<stdin>".to_string(), None)];
+        }
+        self.files
+            .iter()
+            .map(|path| match path.to_str() {
+                Some("-") => ("<stdin>".to_string(), None),
+                _ => (path.display().to_string(), Some(path)),
+            })
+            .collect()
+    }
+
+    fn reader(&self, path: Option<&PathBuf>) -> io::Result<Box<dyn Read>> {
+        Ok(match path {
+            Some(path) => Box::new(File::open(path)?),
+            None => Box::new(io::stdin().lock()),
+        })
+    }
+
+    /// Messages from one input. `Messages` frames heuristic input with
+    /// `StreamParser`, the incremental successor of `MsgStream`'s splitting,
+    /// and handles the other framings as well.
+    fn open(&self, path: Option<&PathBuf>) -> io::Result<Messages<Box<dyn Read>>> {
+        Ok(Messages::new(self.reader(path)?, self.framing))
+    }
+
+    /// Drops the line terminator heuristic framing leaves at the end of a
+    /// message read from a log file. Other framings already remove theirs.
+    fn trim(&self, msg: &mut Message) {
+        if self.framing == Framing::Heuristic {
+            let len = msg.msg.trim_end_matches(['\n', '\r']).len();
+            msg.msg.truncate(len);
+        }
+    }
+}
+
+/// A filter expression, with the error pointed at.
+fn filter_expr(expr: &str) -> Result<Filter, String> {
+    Filter::parse(expr).map_err(|e| format!("{}\n{}", e, e.pointer(expr)))
+}
+
+/// A template, with the error pointed at.
+fn template(template: &str) -> Result<Template, String> {
+    Template::parse(template).map_err(|e| format!("{}\n{}", e, e.pointer(template)))
+}
+
+fn parse(args: ParseArgs) -> io::Result<ExitCode> {
+    let out = io::stdout().lock();
+    let mut output = match args.template {
+        Some(template) => Output::with_template(template, out),
+        None => Output::new(args.format, args.fields, out),
+    };
+    let mut code = ExitCode::SUCCESS;
+    for (name, path) in args.input.sources() {
+        let messages = match args.input.open(path) {
+            Ok(messages) => messages,
+            Err(e) => {
+                eprintln!("{}: {}", name, e);
+                code = ExitCode::from(2);
+                continue;
+            }
+        };
+        for (i, msg) in messages.enumerate() {
+            match msg {
+                Ok(Ok(mut msg)) => {
+                    args.input.trim(&mut msg);
+                    if args
+                        .filter
+                        .as_ref()
+                        .is_none_or(|filter| filter.matches(&msg))
+                    {
+                        output.write(&msg)?;
+                    }
+                }
+                Ok(Err((raw, kind))) => {
+                    eprintln!("{}: message {}: {}: {:?}", name, i + 1, kind, raw);
+                    if code == ExitCode::SUCCESS {
+                        code = ExitCode::from(1);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}: {}", name, e);
+                    code = ExitCode::from(2);
+                    break;
+                }
+            }
+        }
+    }
+    output.finish()?;
+    Ok(code)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Parse(args) => parse(args),
+        Command::Validate(args) => validate::run(args),
+        Command::Listen(args) => listen::run(args),
+        Command::Replay(args) => replay::run(args),
+        Command::Generate(args) => generate::run(args),
+    };
+    match result {
+        Ok(code) => code,
+        // e.g. piped into `head`
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("syslog-parse: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn first_msg(framing: Framing, input: &'static [u8]) -> String {
+        let args = InputArgs {
+            files: vec![],
+            framing,
+        };
+        let mut msg = Messages::new(Cursor::new(input), framing)
+            .next()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        args.trim(&mut msg);
+        msg.msg
+    }
+
+    #[test]
+    fn test_trim() {
+        let input = b"<13>Oct 11 22:14:15 host app: hi\r\n<13>Oct 11 22:14:15 host app: there\n";
+        assert_eq!(first_msg(Framing::Heuristic, input), "hi");
+        let input = b"<13>Oct 11 22:14:15 host app: a\n b \n";
+        assert_eq!(first_msg(Framing::Heuristic, input), "a\n b ");
+        assert_eq!(
+            first_msg(Framing::LF, b"<13>Oct 11 22:14:15 host app: hi \n"),
+            "hi "
+        );
+    }
+}
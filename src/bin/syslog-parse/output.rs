@@ -0,0 +1,230 @@
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+use syslog_parse::field::Field;
+use syslog_parse::message::Message;
+use syslog_parse::template::Template;
+
+/// Widest a table column gets, except the last one.
+const MAX_COLUMN_WIDTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// One JSON object per line
+    Json,
+    Csv,
+    /// Aligned columns, printed once all input is read
+    Table,
+}
+
+pub struct Output<W: Write> {
+    fields: Vec<Field>,
+    out: Writer<W>,
+}
+
+enum Writer<W: Write> {
+    Json(W),
+    Csv(Box<csv::Writer<W>>),
+    Table(W, Vec<Vec<String>>),
+    Template(W, Template),
+}
+
+impl<W: Write> Output<W> {
+    /// Without `fields`, JSON has every field and the other formats the
+    /// usual ones of a log line.
+    pub fn new(format: Format, fields: Vec<Field>, out: W) -> Self {
+        let fields = match (format, fields.is_empty()) {
+            (Format::Json, _) | (_, false) => fields,
+            _ => vec![
+                Field::Timestamp,
+                Field::Hostname,
+                Field::Appname,
+                Field::Severity,
+                Field::Msg,
+            ],
+        };
+        let out = match format {
+            Format::Json => Writer::Json(out),
+            Format::Csv => Writer::Csv(Box::new(csv::Writer::from_writer(out))),
+            Format::Table => Writer::Table(out, Vec::new()),
+        };
+        let mut output = Output { fields, out };
+        let header: Vec<String> = output.fields.iter().map(|f| f.name().to_string()).collect();
+        match &mut output.out {
+            Writer::Csv(_) => output.write_row(header).ok(),
+            Writer::Table(_, rows) => {
+                rows.push(header.iter().map(|name| name.to_uppercase()).collect());
+                None
+            }
+            Writer::Json(_) | Writer::Template(..) => None,
+        };
+        output
+    }
+
+    /// Each message rendered by `template`, which has its own line ends.
+    pub fn with_template(template: Template, out: W) -> Self {
+        Output {
+            fields: Vec::new(),
+            out: Writer::Template(out, template),
+        }
+    }
+
+    pub fn write(&mut self, msg: &Message) -> io::Result<()> {
+        match &mut self.out {
+            Writer::Template(out, template) => {
+                return out.write_all(template.render(msg).as_bytes())
+            }
+            Writer::Json(out) => return writeln!(out, "{}", json(msg, &self.fields)),
+            Writer::Csv(_) | Writer::Table(..) => {}
+        }
+        let row = self
+            .fields
+            .iter()
+            .map(|field| field.get(msg).unwrap_or_default().into_owned())
+            .collect();
+        self.write_row(row)
+    }
+
+    fn write_row(&mut self, row: Vec<String>) -> io::Result<()> {
+        match &mut self.out {
+            Writer::Csv(out) => out.write_record(&row).map_err(io::Error::from),
+            Writer::Table(_, rows) => {
+                rows.push(row);
+                Ok(())
+            }
+            Writer::Json(_) | Writer::Template(..) => Ok(()),
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self.out {
+            Writer::Json(mut out) | Writer::Template(mut out, _) => out.flush(),
+            Writer::Csv(mut out) => out.flush(),
+            Writer::Table(mut out, rows) => {
+                write_table(&mut out, &rows)?;
+                out.flush()
+            }
+        }
+    }
+}
+
+/// The whole message, or the selected fields with NILVALUEs as `null`.
+fn json(msg: &Message, fields: &[Field]) -> Value {
+    if fields.is_empty() {
+        return serde_json::to_value(msg).unwrap_or(Value::Null);
+    }
+    let mut object = Map::new();
+    for field in fields {
+        let value = match field {
+            Field::Sd => serde_json::to_value(&msg.header.structured_data).unwrap_or(Value::Null),
+            Field::Pri => msg.header.pri().into(),
+            Field::Version => msg.header.version.into(),
+            Field::Truncated => msg.truncated.into(),
+            _ => field.get(msg).map(|v| v.into_owned()).into(),
+        };
+        object.insert(field.name().to_string(), value);
+    }
+    Value::Object(object)
+}
+
+fn write_table<W: Write>(out: &mut W, rows: &[Vec<String>]) -> io::Result<()> {
+    let columns = rows.first().map_or(0, Vec::len);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+                .min(MAX_COLUMN_WIDTH)
+        })
+        .collect();
+    for row in rows {
+        let mut line = String::new();
+        for (i, cell) in row.iter().enumerate() {
+            // keep each message on one line
+            let cell = cell.replace(['\n', '\r', '\t'], " ");
+            if i + 1 == columns {
+                line.push_str(&cell);
+            } else {
+                let cell: String = cell.chars().take(widths[i]).collect();
+                line.push_str(&format!("{:<width$}  ", cell, width = widths[i]));
+            }
+        }
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: Format, fields: &[Field]) -> String {
+        let inputs = [
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine evntslog - ID47 [a@1 x="1"] an "event", quoted"#,
+            "<34>Oct 11 22:14:15 host su: 'su root' failed\non two lines",
+        ];
+        let mut out = Vec::new();
+        let mut output = Output::new(format, fields.to_vec(), &mut out);
+        for input in inputs {
+            output.write(&Message::parse(input).unwrap()).unwrap();
+        }
+        output.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_json() {
+        let out = render(Format::Json, &[Field::Pri, Field::Procid, Field::Sd]);
+        assert_eq!(
+            out,
+            concat!(
+                r#"{"pri":165,"procid":null,"sd":[{"id":"a@1","params":[["x","1"]]}]}"#,
+                "\n",
+                r#"{"pri":34,"procid":null,"sd":[]}"#,
+                "\n"
+            )
+        );
+        let full: Value =
+            serde_json::from_str(render(Format::Json, &[]).lines().next().unwrap()).unwrap();
+        assert_eq!(full["hostname"], "mymachine");
+        assert_eq!(full["msg"], r#"an "event", quoted"#);
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            render(Format::Csv, &[]),
+            concat!(
+                "timestamp,hostname,appname,severity,msg\n",
+                r#"2003-10-11T22:14:15.003Z,mymachine,evntslog,notice,"an ""event"", quoted""#,
+                "\n",
+                "Oct 11 22:14:15,host,su,crit,\"'su root' failed\non two lines\"\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            render(Format::Table, &[Field::Hostname, Field::Msgid, Field::Msg]),
+            concat!(
+                "HOSTNAME   MSGID  MSG\n",
+                "mymachine  ID47   an \"event\", quoted\n",
+                "host              'su root' failed on two lines\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_template() {
+        let template = Template::parse(r"%HOSTNAME% %msg%\n").unwrap();
+        let mut out = Vec::new();
+        let mut output = Output::with_template(template, &mut out);
+        output
+            .write(&Message::parse("<34>Oct 11 22:14:15 host su: failed").unwrap())
+            .unwrap();
+        output.finish().unwrap();
+        assert_eq!(out, b"host failed\n");
+    }
+}
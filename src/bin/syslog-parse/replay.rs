@@ -0,0 +1,152 @@
+use crate::InputArgs;
+use clap::{ArgGroup, Args};
+use std::io;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::process::ExitCode;
+use syslog_parse::client::{Client, Destination};
+use syslog_parse::framing::Framing;
+use syslog_parse::replay::{Replayer, Speed};
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("target").required(true)))]
+// capture files from `listen --capture` are octet-counted
+#[command(mut_arg("framing", |arg| arg.default_value("octet-counting")))]
+pub struct ReplayArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Send to this UDP address
+    #[arg(long, group = "target")]
+    udp: Option<SocketAddr>,
+
+    /// Send to this TCP address
+    #[arg(long, group = "target")]
+    tcp: Option<SocketAddr>,
+
+    /// Send to this Unix datagram socket, e.g. /dev/log
+    #[cfg(unix)]
+    #[arg(long, group = "target")]
+    unix: Option<PathBuf>,
+
+    /// Framing of the TCP stream
+    #[arg(long, default_value = "octet-counting")]
+    send_framing: Framing,
+
+    /// Replay this many times faster than captured
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+
+    /// Send as fast as possible, ignoring timestamps
+    #[arg(long, conflicts_with = "speed")]
+    fast: bool,
+
+    /// Replace timestamps with the time of sending
+    #[arg(long)]
+    rewrite_timestamps: bool,
+}
+
+impl ReplayArgs {
+    fn destination(&self) -> Destination {
+        if let Some(addr) = self.udp {
+            return Destination::Udp(addr);
+        }
+        if let Some(addr) = self.tcp {
+            return Destination::Tcp(addr, self.send_framing);
+        }
+        #[cfg(unix)]
+        if let Some(path) = &self.unix {
+            return Destination::UnixDatagram(path.clone());
+        }
+        unreachable!("clap requires a target")
+    }
+
+    fn speed(&self) -> Speed {
+        if self.fast {
+            Speed::Unlimited
+        } else if self.speed == 1.0 {
+            Speed::Original
+        } else {
+            Speed::Scaled(self.speed)
+        }
+    }
+}
+
+pub fn run(args: ReplayArgs) -> io::Result<ExitCode> {
+    if args.speed.is_nan() || args.speed <= 0.0 {
+        eprintln!("syslog-parse: --speed must be above 0");
+        return Ok(ExitCode::from(2));
+    }
+    let mut replayer = Replayer::new(Client::new(args.destination()));
+    replayer.set_speed(args.speed());
+    replayer.set_rewrite_timestamps(args.rewrite_timestamps);
+    for (name, path) in args.input.sources() {
+        let result = args
+            .input
+            .open(path)
+            .and_then(|messages| replayer.replay(messages));
+        if let Err(e) = result {
+            eprintln!("{}: {}", name, e);
+            return Ok(ExitCode::from(2));
+        }
+    }
+    let stats = replayer.stats();
+    eprintln!("{} sent, {} skipped", stats.sent, stats.skipped);
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{listen, Cli, Command};
+    use clap::Parser;
+    use std::fs::File;
+    use std::io::Write;
+    use std::net::UdpSocket;
+    use syslog_parse::server::udp::UdpServer;
+
+    #[test]
+    fn test_capture_replay() {
+        let datagrams: [&[u8]; 3] = [
+            b"<34>Oct 11 22:14:15 host su: first",
+            b"<13>1 2003-10-11T22:14:15.003Z host app - - - two\nlines",
+            b"<13>1 2003-10-11T22:14:16.003Z host app - - - 12 3",
+        ];
+        let mut listener = UdpServer::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let path = std::env::temp_dir().join(format!("capture-{}.log", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        let mut captured = Vec::new();
+        for datagram in datagrams {
+            sender
+                .send_to(datagram, listener.local_addr().unwrap())
+                .unwrap();
+            let received = listener.recv().unwrap();
+            listen::capture(&received, &mut file).unwrap();
+            captured.push(received.message.unwrap());
+        }
+        file.flush().unwrap();
+
+        let mut target = UdpServer::bind("127.0.0.1:0").unwrap();
+        let addr = target.local_addr().unwrap().to_string();
+        let cli = Cli::try_parse_from([
+            "syslog-parse",
+            "replay",
+            "--fast",
+            "--udp",
+            &addr,
+            path.to_str().unwrap(),
+        ])
+        .unwrap();
+        let Command::Replay(args) = cli.command else {
+            panic!("not a replay command");
+        };
+        assert_eq!(run(args).unwrap(), ExitCode::SUCCESS);
+        std::fs::remove_file(&path).unwrap();
+
+        for msg in captured {
+            assert_eq!(target.recv().unwrap().message, Ok(msg));
+        }
+    }
+}
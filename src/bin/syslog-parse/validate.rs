@@ -0,0 +1,470 @@
+use crate::InputArgs;
+use chrono::DateTime;
+use clap::{Args, ValueEnum};
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+use std::rc::Rc;
+use syslog_parse::error::ErrorKind;
+use syslog_parse::message::{Message, Protocol};
+use syslog_parse::reader::Messages;
+
+const BOM: char = '\u{feff}';
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, ValueEnum)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Warning => "warning",
+            Level::Error => "error",
+        })
+    }
+}
+
+/// Rule identifiers, their level and what they check.
+pub const RULES: &[(&str, Level, &str)] = &[
+    (
+        "invalid-header",
+        Level::Error,
+        "no syslog header where a message starts",
+    ),
+    (
+        "invalid-framing",
+        Level::Error,
+        "octet count that is not MSG-LEN SP",
+    ),
+    ("frame-too-large", Level::Error, "frame over the size limit"),
+    (
+        "hostname-length",
+        Level::Error,
+        "RFC5424 HOSTNAME over 255 characters",
+    ),
+    (
+        "appname-length",
+        Level::Error,
+        "RFC5424 APP-NAME over 48 characters",
+    ),
+    (
+        "procid-length",
+        Level::Error,
+        "RFC5424 PROCID over 128 characters",
+    ),
+    (
+        "msgid-length",
+        Level::Error,
+        "RFC5424 MSGID over 32 characters",
+    ),
+    (
+        "hostname-non-ascii",
+        Level::Error,
+        "HOSTNAME outside printable US-ASCII",
+    ),
+    (
+        "header-non-ascii",
+        Level::Error,
+        "RFC5424 APP-NAME, PROCID or MSGID outside printable US-ASCII",
+    ),
+    (
+        "sd-id-duplicate",
+        Level::Error,
+        "the same SD-ID more than once in a message",
+    ),
+    (
+        "sd-name-length",
+        Level::Error,
+        "SD-ID or PARAM-NAME over 32 characters",
+    ),
+    ("msg-bom", Level::Warning, "RFC5424 UTF-8 MSG without a BOM"),
+    (
+        "timestamp-no-year",
+        Level::Warning,
+        "RFC3164 timestamp without a year",
+    ),
+    ("hostname-missing", Level::Warning, "no HOSTNAME"),
+    (
+        "tag-length",
+        Level::Warning,
+        "RFC3164 TAG over 32 characters",
+    ),
+    (
+        "message-length",
+        Level::Warning,
+        "over 1024 bytes for RFC3164, 2048 for RFC5424",
+    ),
+];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub level: Level,
+    pub text: String,
+}
+
+impl Finding {
+    fn new(rule: &'static str, text: String) -> Self {
+        let level = RULES
+            .iter()
+            .find(|(id, _, _)| *id == rule)
+            .map(|(_, level, _)| *level)
+            .unwrap_or(Level::Error);
+        Finding { rule, level, text }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Fail when there are more errors than this
+    #[arg(long, default_value_t = 0)]
+    max_errors: usize,
+
+    /// Fail when there are more warnings than this
+    #[arg(long)]
+    max_warnings: Option<usize>,
+
+    /// Comma separated rules to skip
+    #[arg(long, value_delimiter = ',')]
+    ignore: Vec<String>,
+
+    /// Print the rules and exit
+    #[arg(long)]
+    list_rules: bool,
+}
+
+pub fn run(args: ValidateArgs) -> io::Result<ExitCode> {
+    let mut out = io::stdout().lock();
+    if args.list_rules {
+        for (id, level, about) in RULES {
+            writeln!(out, "{:<20} {:<8} {}", id, level, about)?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(rule) = args
+        .ignore
+        .iter()
+        .find(|rule| !RULES.iter().any(|(id, _, _)| id == rule))
+    {
+        eprintln!("syslog-parse: unknown rule {}", rule);
+        return Ok(ExitCode::from(2));
+    }
+    let ignore: HashSet<&str> = args.ignore.iter().map(String::as_str).collect();
+
+    let (mut messages, mut errors, mut warnings) = (0, 0, 0);
+    for (name, path) in args.input.sources() {
+        let reader = match args.input.reader(path) {
+            Ok(reader) => reader,
+            Err(e) => {
+                eprintln!("{}: {}", name, e);
+                return Ok(ExitCode::from(2));
+            }
+        };
+        let lines = Rc::new(RefCell::new(Lines::new()));
+        let reader = Counted {
+            reader,
+            lines: lines.clone(),
+        };
+        let mut input = Messages::new(reader, args.input.framing);
+        while let Some(msg) = input.next() {
+            let findings = match msg {
+                Ok(Ok(msg)) => lint(&msg, input.frame().len()),
+                Ok(Err((_, kind))) => vec![lint_error(kind)],
+                Err(e) => {
+                    eprintln!("{}: {}", name, e);
+                    return Ok(ExitCode::from(2));
+                }
+            };
+            messages += 1;
+            let (line, column) = lines.borrow_mut().position(input.offset());
+            for finding in findings.iter().filter(|f| !ignore.contains(f.rule)) {
+                match finding.level {
+                    Level::Error => errors += 1,
+                    Level::Warning => warnings += 1,
+                }
+                writeln!(
+                    out,
+                    "{}:{}:{}: {}[{}]: {} (byte {})",
+                    name,
+                    line,
+                    column,
+                    finding.level,
+                    finding.rule,
+                    finding.text,
+                    input.offset()
+                )?;
+            }
+        }
+    }
+    writeln!(
+        out,
+        "{} messages, {} errors, {} warnings",
+        messages, errors, warnings
+    )?;
+    let failed = errors > args.max_errors || args.max_warnings.is_some_and(|max| warnings > max);
+    Ok(if failed {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Line and column of byte offsets, both from 1, counted as the input
+/// is read. Only the lines from the last offset asked for on are kept, so
+/// offsets must not go backwards.
+struct Lines {
+    starts: VecDeque<u64>,
+    /// Lines dropped from the front of `starts`.
+    dropped: usize,
+    read: u64,
+}
+
+impl Lines {
+    fn new() -> Self {
+        Lines {
+            starts: VecDeque::from([0]),
+            dropped: 0,
+            read: 0,
+        }
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        let read = self.read;
+        self.starts
+            .extend(memchr::memchr_iter(b'\n', data).map(|i| read + i as u64 + 1));
+        self.read += data.len() as u64;
+    }
+
+    fn position(&mut self, offset: u64) -> (usize, u64) {
+        while self.starts.get(1).is_some_and(|start| *start <= offset) {
+            self.starts.pop_front();
+            self.dropped += 1;
+        }
+        (self.dropped + 1, offset - self.starts[0] + 1)
+    }
+}
+
+/// Feeds what is read through it to `Lines`.
+struct Counted<R> {
+    reader: R,
+    lines: Rc<RefCell<Lines>>,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.lines.borrow_mut().feed(&buf[..n]);
+        Ok(n)
+    }
+}
+
+pub fn lint_error(kind: ErrorKind) -> Finding {
+    match kind {
+        ErrorKind::Framing => Finding::new("invalid-framing", kind.to_string()),
+        ErrorKind::TooLarge => Finding::new("frame-too-large", kind.to_string()),
+        _ => Finding::new("invalid-header", kind.to_string()),
+    }
+}
+
+/// Findings for a message parsed from `length` bytes.
+pub fn lint(msg: &Message, length: usize) -> Vec<Finding> {
+    let h = &msg.header;
+    let mut findings = Vec::new();
+    let mut check = |failed: bool, rule: &'static str, text: String| {
+        if failed {
+            findings.push(Finding::new(rule, text));
+        }
+    };
+    let chars = |value: &Option<String>| value.as_deref().map_or(0, |v| v.chars().count());
+    let printable = |value: &Option<String>| {
+        value
+            .as_deref()
+            .is_none_or(|v| v.bytes().all(|b| (33..=126).contains(&b)))
+    };
+
+    check(
+        !h.hostname.as_deref().is_none_or(|v| v.is_ascii()),
+        "hostname-non-ascii",
+        format!(
+            "HOSTNAME {:?} is not ASCII",
+            h.hostname.as_deref().unwrap_or_default()
+        ),
+    );
+    check(
+        h.hostname.is_none(),
+        "hostname-missing",
+        "no HOSTNAME".to_string(),
+    );
+    match h.protocol {
+        Protocol::Rfc5424 => {
+            for (rule, field, value, max) in [
+                ("hostname-length", "HOSTNAME", &h.hostname, 255),
+                ("appname-length", "APP-NAME", &h.appname, 48),
+                ("procid-length", "PROCID", &h.procid, 128),
+                ("msgid-length", "MSGID", &h.msgid, 32),
+            ] {
+                let n = chars(value);
+                check(
+                    n > max,
+                    rule,
+                    format!("{} has {} characters, at most {} allowed", field, n, max),
+                );
+            }
+            for (field, value) in [
+                ("APP-NAME", &h.appname),
+                ("PROCID", &h.procid),
+                ("MSGID", &h.msgid),
+            ] {
+                check(
+                    !printable(value),
+                    "header-non-ascii",
+                    format!(
+                        "{} {:?} is not printable US-ASCII",
+                        field,
+                        value.as_deref().unwrap_or_default()
+                    ),
+                );
+            }
+            let mut seen = HashSet::new();
+            for element in &h.structured_data {
+                check(
+                    !seen.insert(&element.id),
+                    "sd-id-duplicate",
+                    format!("SD-ID {} appears more than once", element.id),
+                );
+                for name in
+                    std::iter::once(&element.id).chain(element.params.iter().map(|(name, _)| name))
+                {
+                    let n = name.chars().count();
+                    check(
+                        n > 32,
+                        "sd-name-length",
+                        format!("{} has {} characters, at most 32 allowed", name, n),
+                    );
+                }
+            }
+            check(
+                !msg.msg.is_ascii() && !msg.msg.starts_with(BOM),
+                "msg-bom",
+                "MSG is UTF-8 but does not start with a BOM".to_string(),
+            );
+            check(
+                length > 2048,
+                "message-length",
+                format!("{} bytes, receivers need only accept 2048", length),
+            );
+        }
+        Protocol::Rfc3164 => {
+            check(
+                !has_year(&h.timestamp),
+                "timestamp-no-year",
+                format!("TIMESTAMP {:?} has no year", h.timestamp),
+            );
+            let tag = chars(&h.appname) + h.procid.as_deref().map_or(0, |p| p.chars().count() + 2);
+            check(
+                tag > 32,
+                "tag-length",
+                format!("TAG has {} characters, at most 32 allowed", tag),
+            );
+            check(
+                length > 1024,
+                "message-length",
+                format!("{} bytes, at most 1024 allowed", length),
+            );
+        }
+    }
+    findings
+}
+
+/// Whether an RFC3164 TIMESTAMP carries a year: RFC3339, or a year
+/// between the date and the time.
+fn has_year(timestamp: &str) -> bool {
+    let timestamp = timestamp.trim_end_matches(':');
+    DateTime::parse_from_rfc3339(timestamp).is_ok()
+        || timestamp
+            .split_whitespace()
+            .any(|part| part.len() == 4 && part.bytes().all(|b| b.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(input: &str) -> Vec<&'static str> {
+        lint(&Message::parse(input).unwrap(), input.len())
+            .into_iter()
+            .map(|f| f.rule)
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        assert_eq!(
+            rules("<34>1 2003-10-11T22:14:15.003Z host app 1 ID - \u{feff}héllo"),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            rules(&format!(
+                "<34>1 2003-10-11T22:14:15.003Z hôst {} 1 ID [a@1 x=\"1\"][a@1 {}=\"2\"] héllo",
+                "a".repeat(49),
+                "p".repeat(33)
+            )),
+            vec![
+                "hostname-non-ascii",
+                "appname-length",
+                "sd-id-duplicate",
+                "sd-name-length",
+                "msg-bom"
+            ]
+        );
+        assert_eq!(
+            rules("<34>Oct 11 22:14:15 host su: ok"),
+            vec!["timestamp-no-year"]
+        );
+        assert_eq!(
+            rules("<34>Oct 11 22:14:15 host su: 2023 was a good year"),
+            vec!["timestamp-no-year"]
+        );
+        assert_eq!(
+            rules("<34>2003-10-11T22:14:15.003Z host su: ok"),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            rules(&format!(
+                "<34>Oct 11 2023 22:14:15 {}: {}",
+                "t".repeat(33),
+                "m".repeat(1024)
+            )),
+            vec!["hostname-missing", "tag-length", "message-length"]
+        );
+        // measured on the frame as received, not on the parsed message
+        let msg = Message::parse("<34>Oct 11 2023 22:14:15 host su: ok").unwrap();
+        assert_eq!(lint(&msg, 1025)[0].rule, "message-length");
+        assert_eq!(lint_error(ErrorKind::Header).rule, "invalid-header");
+        assert!(RULES.iter().all(|(_, _, about)| !about.is_empty()));
+    }
+
+    #[test]
+    fn test_lines() {
+        let lines = Rc::new(RefCell::new(Lines::new()));
+        let mut reader = Counted {
+            reader: &b"ab\ncd\n\ne"[..],
+            lines: lines.clone(),
+        };
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(lines.borrow_mut().position(0), (1, 1));
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        let mut lines = reader.lines.borrow_mut();
+        assert_eq!(lines.position(4), (2, 2));
+        assert_eq!(lines.position(7), (4, 1));
+        assert_eq!(lines.starts.len(), 1);
+    }
+}
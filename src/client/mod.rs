@@ -0,0 +1,488 @@
+//! Syslog senders.
+//!
+//! `Client` writes messages with `Message`'s `Display`, so whatever it
+//! sends parses back with this crate.
+
+#[cfg(feature = "tls")]
+pub mod tls;
+
+use crate::clock::{Clock, SystemClock};
+use crate::framing::Framing;
+use crate::message::Message;
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::{UnixDatagram, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Somewhere messages can be written to.
+pub trait Sink: Send {
+    fn send(&mut self, msg: &Message) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Sink for Client {
+    fn send(&mut self, msg: &Message) -> io::Result<()> {
+        Client::send(self, msg)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Client::flush(self)
+    }
+}
+
+/// Fails once the receiving side is gone.
+impl Sink for Sender<Message> {
+    fn send(&mut self, msg: &Message) -> io::Result<()> {
+        Sender::send(self, msg.clone())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "receiver is gone"))
+    }
+}
+
+impl Sink for Vec<Message> {
+    fn send(&mut self, msg: &Message) -> io::Result<()> {
+        self.push(msg.clone());
+        Ok(())
+    }
+}
+
+/// Where a `Client` sends to.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Destination {
+    /// One message per datagram, RFC5426.
+    Udp(SocketAddr),
+    Tcp(SocketAddr, Framing),
+    #[cfg(unix)]
+    UnixDatagram(PathBuf),
+    #[cfg(unix)]
+    UnixStream(PathBuf, Framing),
+}
+
+/// Counters of a `Client`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ClientStats {
+    pub sent: u64,
+    /// Messages thrown away because the queue was full.
+    pub dropped: u64,
+    /// Failed connection attempts and writes.
+    pub failures: u64,
+    /// Messages thrown away because the destination can never take them,
+    /// e.g. datagrams over its size limit.
+    pub rejected: u64,
+}
+
+/// Sender with reconnection.
+///
+/// Messages go through a bounded queue. While the destination is down
+/// they wait there, the oldest being dropped when it is full, and a new
+/// connection is attempted on each `send` or `flush` once the backoff
+/// delay, doubled after each failure, has passed. A message the
+/// destination refuses whatever the connection, such as a datagram that is
+/// too large, is dropped instead of being retried.
+///
+/// With non-transparent framing, trailer bytes inside a message are
+/// replaced by spaces so they cannot end it early. Heuristic framing sends
+/// messages back to back, which is only as reliable as the receiver's
+/// heuristic; prefer octet counting.
+#[derive(Debug)]
+pub struct Client {
+    destination: Destination,
+    conn: Option<Conn>,
+    queue: VecDeque<String>,
+    max_queued: usize,
+    buf: Vec<u8>,
+    timeout: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    backoff: Duration,
+    next_attempt: Option<DateTime<Local>>,
+    last_error: Option<io::Error>,
+    stats: ClientStats,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Debug)]
+enum Conn {
+    Udp(UdpSocket),
+    Tcp(TcpStream, Framing),
+    #[cfg(unix)]
+    UnixDatagram(UnixDatagram),
+    #[cfg(unix)]
+    UnixStream(UnixStream, Framing),
+}
+
+impl Client {
+    /// A client that connects on first use.
+    pub fn new(destination: Destination) -> Self {
+        let initial_backoff = Duration::from_millis(100);
+        Client {
+            destination,
+            conn: None,
+            queue: VecDeque::new(),
+            max_queued: 1000,
+            buf: Vec::new(),
+            timeout: Duration::from_secs(5),
+            initial_backoff,
+            max_backoff: Duration::from_secs(30),
+            backoff: initial_backoff,
+            next_attempt: None,
+            last_error: None,
+            stats: ClientStats::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn destination(&self) -> &Destination {
+        &self.destination
+    }
+
+    /// Messages kept while the destination is down, 1000 by default.
+    pub fn set_max_queued(&mut self, max: usize) {
+        self.max_queued = max;
+        self.trim_queue(max);
+    }
+
+    /// Connect and write timeout for stream destinations.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Delay after the first failure, and the most it grows to.
+    pub fn set_backoff(&mut self, initial: Duration, max: Duration) {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self.backoff = initial;
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn stats(&self) -> ClientStats {
+        self.stats
+    }
+
+    /// Messages waiting to be sent.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Queue a message and send what can be sent.
+    pub fn send(&mut self, msg: &Message) -> io::Result<()> {
+        self.send_str(&msg.to_string())
+    }
+
+    /// `send` for an already serialized message.
+    pub fn send_str(&mut self, msg: &str) -> io::Result<()> {
+        let msg = match self.framing() {
+            Some(Framing::NonTransparent(trailer)) if trailer.is_ascii() => {
+                msg.replace(trailer as char, " ")
+            }
+            _ => msg.to_string(),
+        };
+        self.trim_queue(self.max_queued.saturating_sub(1));
+        if self.max_queued == 0 {
+            self.stats.dropped += 1;
+            return Ok(());
+        }
+        self.queue.push_back(msg);
+        self.flush()
+    }
+
+    /// Send the queued messages. Fails with the last connection or write
+    /// error while messages are left in the queue, or with the error of a
+    /// message that was rejected.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut rejected = None;
+        while let Some(msg) = self.queue.front() {
+            if self.conn.is_none() {
+                if let Some(next_attempt) = self.next_attempt {
+                    if self.clock.now() < next_attempt {
+                        return Err(self.pending_error());
+                    }
+                }
+                match self.connect() {
+                    Ok(conn) => self.conn = Some(conn),
+                    Err(e) => {
+                        self.fail(e);
+                        return Err(self.pending_error());
+                    }
+                }
+            }
+            let conn = self.conn.as_mut().unwrap();
+            match conn.write(msg, &mut self.buf) {
+                Ok(()) => {
+                    self.queue.pop_front();
+                    self.stats.sent += 1;
+                    self.backoff = self.initial_backoff;
+                    self.next_attempt = None;
+                    self.last_error = None;
+                }
+                Err(e) if is_rejected(&e) => {
+                    self.queue.pop_front();
+                    self.stats.rejected += 1;
+                    rejected = Some(e);
+                }
+                Err(e) => {
+                    self.conn = None;
+                    self.fail(e);
+                    return Err(self.pending_error());
+                }
+            }
+        }
+        match rejected {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn framing(&self) -> Option<Framing> {
+        match &self.destination {
+            Destination::Tcp(_, framing) => Some(*framing),
+            #[cfg(unix)]
+            Destination::UnixStream(_, framing) => Some(*framing),
+            _ => None,
+        }
+    }
+
+    fn trim_queue(&mut self, len: usize) {
+        while self.queue.len() > len {
+            self.queue.pop_front();
+            self.stats.dropped += 1;
+        }
+    }
+
+    fn fail(&mut self, e: io::Error) {
+        self.stats.failures += 1;
+        self.next_attempt = Some(self.clock.now() + self.backoff);
+        self.backoff = (self.backoff * 2).min(self.max_backoff);
+        self.last_error = Some(e);
+    }
+
+    fn pending_error(&self) -> io::Error {
+        match &self.last_error {
+            Some(e) => io::Error::new(e.kind(), e.to_string()),
+            None => io::Error::new(io::ErrorKind::NotConnected, "waiting to reconnect"),
+        }
+    }
+
+    fn connect(&self) -> io::Result<Conn> {
+        Ok(match &self.destination {
+            Destination::Udp(addr) => {
+                let local: SocketAddr = match addr {
+                    SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+                    SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+                };
+                let socket = UdpSocket::bind(local)?;
+                socket.connect(addr)?;
+                Conn::Udp(socket)
+            }
+            Destination::Tcp(addr, framing) => {
+                let stream = TcpStream::connect_timeout(addr, self.timeout)?;
+                stream.set_write_timeout(Some(self.timeout))?;
+                stream.set_nodelay(true)?;
+                Conn::Tcp(stream, *framing)
+            }
+            #[cfg(unix)]
+            Destination::UnixDatagram(path) => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(path)?;
+                Conn::UnixDatagram(socket)
+            }
+            #[cfg(unix)]
+            Destination::UnixStream(path, framing) => {
+                let stream = UnixStream::connect(path)?;
+                stream.set_write_timeout(Some(self.timeout))?;
+                Conn::UnixStream(stream, *framing)
+            }
+        })
+    }
+}
+
+impl Conn {
+    fn write(&mut self, msg: &str, buf: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            Conn::Udp(socket) => socket.send(msg.as_bytes()).map(drop),
+            #[cfg(unix)]
+            Conn::UnixDatagram(socket) => socket.send(msg.as_bytes()).map(drop),
+            Conn::Tcp(stream, framing) => write_framed(stream, *framing, msg, buf),
+            #[cfg(unix)]
+            Conn::UnixStream(stream, framing) => write_framed(stream, *framing, msg, buf),
+        }
+    }
+}
+
+/// `EMSGSIZE`, which has no `io::ErrorKind` of its own.
+#[cfg(unix)]
+const EMSGSIZE: i32 = libc::EMSGSIZE;
+/// `WSAEMSGSIZE`.
+#[cfg(windows)]
+const EMSGSIZE: i32 = 10040;
+
+/// Whether a write failed because of the message rather than the
+/// connection, so that retrying cannot help.
+fn is_rejected(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::InvalidInput || e.raw_os_error() == Some(EMSGSIZE)
+}
+
+fn write_framed<W: Write>(
+    out: &mut W,
+    framing: Framing,
+    msg: &str,
+    buf: &mut Vec<u8>,
+) -> io::Result<()> {
+    buf.clear();
+    framing.encode(msg, buf)?;
+    out.write_all(buf)
+}
+
+/// The kernel's hostname, or `$HOSTNAME`.
+pub fn local_hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// The name of the running executable, for APP-NAME.
+pub fn process_name() -> Option<String> {
+    std::env::current_exe()
+        .ok()?
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::server::tcp::TcpServer;
+    use crate::server::udp::UdpServer;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn message(text: &str) -> Message {
+        let mut msg =
+            Message::parse("<34>1 2003-10-11T22:14:15.003Z mymachine su - ID47 [a b=\"]\"]")
+                .unwrap();
+        msg.msg = text.to_string();
+        msg
+    }
+
+    #[test]
+    fn test_udp() {
+        let mut server = UdpServer::bind("127.0.0.1:0").unwrap();
+        let mut client = Client::new(Destination::Udp(server.local_addr().unwrap()));
+        let msg = message("over udp\n");
+        client.send(&msg).unwrap();
+        // a trailing LF is taken as a trailer by the receiver
+        assert_eq!(server.recv().unwrap().message, Ok(message("over udp")));
+        assert_eq!(client.stats().sent, 1);
+    }
+
+    #[test]
+    fn test_udp_too_large() {
+        let mut server = UdpServer::bind("127.0.0.1:0").unwrap();
+        let mut client = Client::new(Destination::Udp(server.local_addr().unwrap()));
+        let error = client.send_str(&"x".repeat(70 * 1024)).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(EMSGSIZE));
+        assert_eq!(client.queued(), 0);
+        client.send(&message("small")).unwrap();
+        assert_eq!(server.recv().unwrap().message, Ok(message("small")));
+        assert_eq!(
+            client.stats(),
+            ClientStats {
+                sent: 1,
+                rejected: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_reconnect() {
+        // find a free port, and leave it closed for now
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let clock = ManualClock::default();
+        let mut client = Client::new(Destination::Tcp(addr, Framing::LF));
+        client.set_clock(Arc::new(clock.clone()));
+        client.set_max_queued(2);
+        client.set_backoff(Duration::from_secs(1), Duration::from_secs(3));
+
+        for text in ["one", "two\nlines", "three"] {
+            assert!(client.send(&message(text)).is_err());
+        }
+        assert_eq!(client.queued(), 2);
+        assert_eq!(
+            client.stats(),
+            ClientStats {
+                sent: 0,
+                dropped: 1,
+                failures: 1,
+                rejected: 0,
+            }
+        );
+
+        let mut server = TcpServer::bind(addr).unwrap();
+        server.set_framing(Framing::LF);
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || server.run(tx));
+
+        // still backing off
+        assert_eq!(
+            client.flush().unwrap_err().kind(),
+            io::ErrorKind::ConnectionRefused
+        );
+        assert_eq!(client.stats().failures, 1);
+        clock.advance(chrono::Duration::seconds(1));
+        client.flush().unwrap();
+        assert_eq!(client.stats().sent, 2);
+
+        assert_eq!(rx.recv().unwrap().message, Ok(message("two lines")));
+        assert_eq!(rx.recv().unwrap().message, Ok(message("three")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_stream() {
+        use crate::server::unix::UnixStreamServer;
+
+        let path = std::env::temp_dir().join(format!("syslog-parse-{}-client", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut server = UnixStreamServer::bind(&path).unwrap();
+        server.set_framing(Framing::OctetCounting);
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || server.run(tx));
+
+        let mut client = Client::new(Destination::UnixStream(
+            path.clone(),
+            Framing::OctetCounting,
+        ));
+        let sent = [
+            message("two\nlines"),
+            message("<13>1 - - - - - - not a header"),
+        ];
+        for msg in &sent {
+            client.send(msg).unwrap();
+        }
+        for msg in sent {
+            assert_eq!(rx.recv().unwrap().message, Ok(msg));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+use super::Sink;
+use crate::framing::Framing;
+use crate::message::Message;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+use std::io::{self, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+
+/// RFC5425 sender: TLS with octet counting framing.
+#[derive(Debug)]
+pub struct TlsClient {
+    stream: StreamOwned<ClientConnection, TcpStream>,
+    buf: Vec<u8>,
+}
+
+impl TlsClient {
+    /// Connect and complete the handshake. `server_name` is checked against
+    /// the server certificate.
+    pub fn connect<A: ToSocketAddrs>(
+        addr: A,
+        server_name: &str,
+        config: Arc<ClientConfig>,
+    ) -> io::Result<Self> {
+        let name = ServerName::try_from(server_name.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut conn = ClientConnection::new(config, name).map_err(io::Error::other)?;
+        let mut stream = TcpStream::connect(addr)?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut stream)?;
+        }
+        Ok(TlsClient {
+            stream: StreamOwned::new(conn, stream),
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn send(&mut self, msg: &Message) -> io::Result<()> {
+        self.send_str(&msg.to_string())
+    }
+
+    /// Send an already serialized message.
+    pub fn send_str(&mut self, msg: &str) -> io::Result<()> {
+        self.buf.clear();
+        Framing::OctetCounting.encode(msg, &mut self.buf)?;
+        self.stream.write_all(&self.buf)?;
+        self.stream.flush()
+    }
+
+    /// Send `close_notify` and close the connection.
+    pub fn close(mut self) -> io::Result<()> {
+        self.stream.conn.send_close_notify();
+        self.stream.flush()?;
+        self.stream.sock.shutdown(Shutdown::Write)
+    }
+}
+
+impl Sink for TlsClient {
+    fn send(&mut self, msg: &Message) -> io::Result<()> {
+        TlsClient::send(self, msg)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, Duration, Local};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+/// Source of the current time for the stream's idle-flush timer.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// The wall clock, `Local::now()`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time,
+/// so a test can keep one handle and hand the other to a `MsgStream`.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<DateTime<Local>>>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new(Local::now())
+    }
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Local>) -> Self {
+        ManualClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Local>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::default();
+        let shared = clock.clone();
+        let start = clock.now();
+        shared.advance(Duration::seconds(3));
+        assert_eq!(
+            clock.now().signed_duration_since(start),
+            Duration::seconds(3)
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::framing::{FrameDecoder, Framing};
+use crate::message::Message;
+use crate::stream::MessageResult;
+use bytes::{BufMut, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// `tokio_util` codec over `FrameDecoder`, so that
+/// `Framed<TcpStream, SyslogCodec>` yields parsed messages and accepts
+/// messages to send.
+///
+/// Messages that fail to parse are items too, as `Err((raw, kind))`; the
+/// codec only fails on I/O errors.
+#[derive(Debug, Default)]
+pub struct SyslogCodec {
+    decoder: FrameDecoder,
+}
+
+impl SyslogCodec {
+    pub fn new(framing: Framing) -> Self {
+        SyslogCodec {
+            decoder: FrameDecoder::new(framing),
+        }
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.decoder.framing()
+    }
+
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.decoder.set_max_frame_len(len);
+    }
+}
+
+impl Decoder for SyslogCodec {
+    type Item = MessageResult;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<MessageResult>> {
+        if !src.is_empty() {
+            self.decoder.feed(src);
+            src.clear();
+        }
+        Ok(self.decoder.next_message())
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<MessageResult>> {
+        if let Some(item) = self.decode(src)? {
+            return Ok(Some(item));
+        }
+        self.decoder.finish();
+        Ok(self.decoder.next_message())
+    }
+}
+
+impl Encoder<&Message> for SyslogCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: &Message, dst: &mut BytesMut) -> io::Result<()> {
+        self.encode(item.to_string().as_str(), dst)
+    }
+}
+
+impl Encoder<Message> for SyslogCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Message, dst: &mut BytesMut) -> io::Result<()> {
+        self.encode(&item, dst)
+    }
+}
+
+/// Already serialized messages, framed as configured.
+impl Encoder<&str> for SyslogCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: &str, dst: &mut BytesMut) -> io::Result<()> {
+        self.framing().encode(item, &mut dst.writer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::codec::Framed;
+
+    const MSG: &str =
+        "<34>1 2003-10-11T22:14:15.003Z mymachine.example.com su - ID47 - 'su root' failed";
+
+    #[tokio::test]
+    async fn test_framed() {
+        for framing in [Framing::Heuristic, Framing::OctetCounting, Framing::LF] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let msg = Message::parse(MSG).unwrap();
+
+            let sent = msg.clone();
+            let client = tokio::spawn(async move {
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let mut framed = Framed::new(stream, SyslogCodec::new(framing));
+                framed.send(&sent).await.unwrap();
+                framed.send(sent).await.unwrap();
+                framed.send("garbage").await.unwrap();
+            });
+
+            let (stream, _) = listener.accept().await.unwrap();
+            let received: Vec<_> = Framed::new(stream, SyslogCodec::new(framing))
+                .map(Result::unwrap)
+                .collect()
+                .await;
+            client.await.unwrap();
+
+            match framing {
+                // without framing the garbage is taken as part of the message
+                Framing::Heuristic => {
+                    let mut last = msg.clone();
+                    last.msg.push_str("garbage");
+                    assert_eq!(received, vec![Ok(msg), Ok(last)]);
+                }
+                _ => assert_eq!(
+                    received,
+                    vec![
+                        Ok(msg.clone()),
+                        Ok(msg),
+                        Err(("garbage".to_string(), ErrorKind::Header))
+                    ]
+                ),
+            }
+        }
+    }
+}
@@ -16,7 +16,91 @@ pub enum SyslogParseError<'a> {
     TimeParseError,
 }
 
-#[derive(Debug, Display)]
+/// A name that `FromStr` does not know, e.g. a facility or a field.
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[error("未知的{kind}: {name}")]
+pub struct UnknownName {
+    pub kind: &'static str,
+    pub name: String,
+}
+
+impl UnknownName {
+    pub fn new(kind: &'static str, name: &str) -> Self {
+        UnknownName {
+            kind,
+            name: name.to_string(),
+        }
+    }
+}
+
+/// What is wrong in a filter expression or selector.
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum ExprErrorKind {
+    #[error("意外的字符 {0:?}")]
+    UnexpectedChar(char),
+    #[error("意外的结尾")]
+    UnexpectedEnd,
+    #[error("期待{expected}，得到 {found}")]
+    Expected {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("字符串没有结束的引号")]
+    UnterminatedString,
+    #[error(transparent)]
+    UnknownName(#[from] UnknownName),
+    #[error("{0}不是数字")]
+    Number(String),
+    #[error("正则表达式格式不符合: {0}")]
+    Regex(String),
+    #[error("CIDR格式不符合，期待10.0.0.0/8、fe80::/10等: {0}")]
+    Cidr(String),
+    #[error("{field}不支持运算符{op}")]
+    Operator { field: String, op: &'static str },
+}
+
+/// An error in a filter expression or selector, at a byte offset.
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+#[error("第{}个字符: {kind}", .position + 1)]
+pub struct ExprError {
+    pub position: usize,
+    pub kind: ExprErrorKind,
+}
+
+impl ExprError {
+    pub fn new(position: usize, kind: ExprErrorKind) -> Self {
+        ExprError { position, kind }
+    }
+
+    /// The expression with a caret under the error, for terminals.
+    pub fn pointer(&self, expr: &str) -> String {
+        let column = expr
+            .get(..self.position)
+            .map_or(self.position, |before| before.chars().count());
+        format!("{}\n{}^", expr, " ".repeat(column))
+    }
+}
+
+/// A `Generator` setting that it cannot use.
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum GeneratorError {
+    #[error("权重不能全为0")]
+    ZeroWeights,
+    #[error("{field}应为1到{max}个字符，且不能是-: {value:?}")]
+    Length {
+        field: &'static str,
+        value: String,
+        max: usize,
+    },
+    #[error("{field}不能包含{found:?}: {value:?}")]
+    Char {
+        field: &'static str,
+        value: String,
+        found: char,
+    },
+}
+
+#[derive(Debug, Display, Clone, Copy, Eq, PartialEq)]
 pub enum ErrorKind {
     #[strum(serialize = "优先级格式不符合，希望facility范围:0-23，severity范围：0-7")]
     Pri,
@@ -32,11 +116,17 @@ pub enum ErrorKind {
     ProcessId,
     #[strum(serialize = "Message Id格式不符合")]
     MessageId,
+    #[strum(serialize = "未找到syslog报文头")]
+    Header,
+    #[strum(serialize = "报文超过长度限制")]
+    TooLarge,
+    #[strum(serialize = "帧长度格式不符合，期待MSG-LEN SP SYSLOG-MSG")]
+    Framing,
 }
 
 pub trait New {
     #[allow(clippy::new_ret_no_self)]
-    fn new(input: &str, code: nom::error::ErrorKind) -> NomError {
+    fn new(input: &str, code: nom::error::ErrorKind) -> NomError<'_> {
         nom::Err::Error(Error::new(input, code))
     }
 }
@@ -0,0 +1,143 @@
+use crate::error::UnknownName;
+use crate::message::Message;
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// A part of a message that can be selected by name, e.g. for output
+/// columns.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Field {
+    Protocol,
+    Pri,
+    Facility,
+    Severity,
+    Version,
+    Timestamp,
+    Hostname,
+    Appname,
+    Procid,
+    Msgid,
+    /// All structured data elements, as they are written in RFC5424.
+    Sd,
+    Msg,
+    Truncated,
+}
+
+impl Field {
+    pub const ALL: [Field; 13] = [
+        Field::Protocol,
+        Field::Pri,
+        Field::Facility,
+        Field::Severity,
+        Field::Version,
+        Field::Timestamp,
+        Field::Hostname,
+        Field::Appname,
+        Field::Procid,
+        Field::Msgid,
+        Field::Sd,
+        Field::Msg,
+        Field::Truncated,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Protocol => "protocol",
+            Field::Pri => "pri",
+            Field::Facility => "facility",
+            Field::Severity => "severity",
+            Field::Version => "version",
+            Field::Timestamp => "timestamp",
+            Field::Hostname => "hostname",
+            Field::Appname => "appname",
+            Field::Procid => "procid",
+            Field::Msgid => "msgid",
+            Field::Sd => "sd",
+            Field::Msg => "msg",
+            Field::Truncated => "truncated",
+        }
+    }
+
+    /// The field's value as text, `None` for a NILVALUE or missing field.
+    pub fn get<'a>(&self, msg: &'a Message) -> Option<Cow<'a, str>> {
+        let h = &msg.header;
+        let text = |value: &'a Option<String>| value.as_deref().map(Cow::Borrowed);
+        match self {
+            Field::Protocol => Some(h.protocol.to_string().into()),
+            Field::Pri => Some(h.pri().to_string().into()),
+            Field::Facility => Some(h.facility.to_string().into()),
+            Field::Severity => Some(h.severity.to_string().into()),
+            Field::Version => h.version.map(|v| v.to_string().into()),
+            Field::Timestamp => Some(h.timestamp.as_str().into()),
+            Field::Hostname => text(&h.hostname),
+            Field::Appname => text(&h.appname),
+            Field::Procid => text(&h.procid),
+            Field::Msgid => text(&h.msgid),
+            Field::Sd if h.structured_data.is_empty() => None,
+            Field::Sd => Some(
+                h.structured_data
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<String>()
+                    .into(),
+            ),
+            Field::Msg => Some(msg.msg.as_str().into()),
+            Field::Truncated => Some(msg.truncated.to_string().into()),
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, UnknownName> {
+        Field::ALL
+            .into_iter()
+            .find(|field| field.name() == s)
+            .ok_or_else(|| UnknownName::new("field", s))
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let msg = Message::parse(
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine evntslog - ID47 [a@1 x="1"][b@1] hi"#,
+        )
+        .unwrap();
+        let values: Vec<_> = Field::ALL.iter().map(|f| f.get(&msg)).collect();
+        assert_eq!(
+            values,
+            [
+                Some("rfc5424"),
+                Some("165"),
+                Some("local4"),
+                Some("notice"),
+                Some("1"),
+                Some("2003-10-11T22:14:15.003Z"),
+                Some("mymachine"),
+                Some("evntslog"),
+                None,
+                Some("ID47"),
+                Some(r#"[a@1 x="1"][b@1]"#),
+                Some("hi"),
+                Some("false"),
+            ]
+            .map(|v| v.map(Cow::Borrowed))
+        );
+        for field in Field::ALL {
+            assert_eq!(field.name().parse(), Ok(field));
+        }
+        assert!("host".parse::<Field>().is_err());
+    }
+}
@@ -0,0 +1,664 @@
+//! A small expression language over parsed messages.
+//!
+//! ```
+//! use syslog_parse::filter::{Filter, Predicate};
+//! use syslog_parse::message::Message;
+//!
+//! let filter: Filter = r#"severity <= warning and app == "sshd"
+//!     and sd["origin"]["ip"] =~ "^10\.""#
+//!     .parse()
+//!     .unwrap();
+//! let msg = Message::parse(
+//!     r#"<36>1 2024-03-01T12:00:00Z web1 sshd 42 - [origin ip="10.0.0.7"] failed"#,
+//! )
+//! .unwrap();
+//! assert!(filter.matches(&msg));
+//! ```
+//!
+//! Grammar, keywords being case-insensitive:
+//!
+//! ```text
+//! expr    = or
+//! or      = and (("or" | "||") and)*
+//! and     = not (("and" | "&&") not)*
+//! not     = ("not" | "!") not | primary
+//! primary = "(" expr ")" | "true" | "false" | operand [op value]
+//! operand = field | sd["id"] | sd["id"]["param"]
+//! op      = "==" | "!=" | "<" | "<=" | ">" | ">=" | "=~" | "!~" | "contains" | "in"
+//! value   = "quoted string" | bare-word
+//! ```
+//!
+//! Fields are the names of `Field`, plus `host` and `app`. An operand
+//! alone checks that the field is present. Facility and severity compare
+//! by code, so `severity <= warning` is warning or more severe. `in`
+//! checks that the operand is an IP address in a CIDR range. A missing
+//! field fails every test except `!=` and `!~`.
+
+use crate::error::{ExprError, ExprErrorKind};
+use crate::field::Field;
+use crate::message::{Facility, Message, Severity};
+use regex::Regex;
+use std::borrow::Cow;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A yes or no question about a message.
+pub trait Predicate: Send + Sync {
+    fn matches(&self, msg: &Message) -> bool;
+}
+
+impl<F: Fn(&Message) -> bool + Send + Sync> Predicate for F {
+    fn matches(&self, msg: &Message) -> bool {
+        self(msg)
+    }
+}
+
+/// A compiled filter expression.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(expr: &str) -> Result<Filter, ExprError> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?,
+            pos: 0,
+            end: expr.len(),
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(Filter { expr }),
+            Some((at, token)) => Err(ExprError::new(
+                at,
+                ExprErrorKind::Expected {
+                    expected: "and、or或结尾",
+                    found: token.to_string(),
+                },
+            )),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, ExprError> {
+        Filter::parse(s)
+    }
+}
+
+impl Predicate for Filter {
+    fn matches(&self, msg: &Message) -> bool {
+        self.expr.eval(msg)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Const(bool),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test(Operand, Test),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Field(Field),
+    Sd(String, Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Test {
+    Exists,
+    Number(Op, u32),
+    Text(Op, String),
+    Regex(Regex, bool),
+    Contains(String),
+    In(Cidr),
+}
+
+impl Operand {
+    /// Facility and severity codes, PRI and VERSION.
+    fn number(&self, msg: &Message) -> Option<u32> {
+        let h = &msg.header;
+        match self {
+            Operand::Field(Field::Facility) => Some(h.facility.code() as u32),
+            Operand::Field(Field::Severity) => Some(h.severity.code() as u32),
+            Operand::Field(Field::Pri) => Some(h.pri() as u32),
+            Operand::Field(Field::Version) => h.version,
+            _ => None,
+        }
+    }
+
+    fn text<'a>(&self, msg: &'a Message) -> Option<Cow<'a, str>> {
+        match self {
+            Operand::Field(field) => field.get(msg),
+            Operand::Sd(id, None) => msg.header.sd_element(id).map(|e| e.to_string().into()),
+            Operand::Sd(id, Some(param)) => msg
+                .header
+                .sd_element(id)
+                .and_then(|e| e.param(param))
+                .map(Cow::Borrowed),
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Operand::Field(Field::Facility | Field::Severity | Field::Pri | Field::Version)
+        )
+    }
+}
+
+impl Op {
+    fn eval<T: Ord + ?Sized>(self, left: &T, right: &T) -> bool {
+        match self {
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, msg: &Message) -> bool {
+        match self {
+            Expr::Const(value) => *value,
+            Expr::And(a, b) => a.eval(msg) && b.eval(msg),
+            Expr::Or(a, b) => a.eval(msg) || b.eval(msg),
+            Expr::Not(a) => !a.eval(msg),
+            Expr::Test(operand, test) => {
+                let missing = matches!(test, Test::Number(Op::Ne, _) | Test::Text(Op::Ne, _))
+                    || matches!(test, Test::Regex(_, true));
+                match test {
+                    Test::Number(op, value) => {
+                        operand.number(msg).map_or(missing, |n| op.eval(&n, value))
+                    }
+                    test => match operand.text(msg) {
+                        None => missing,
+                        Some(text) => match test {
+                            Test::Exists => true,
+                            Test::Text(op, value) => op.eval(text.as_ref(), value.as_str()),
+                            Test::Regex(regex, negate) => regex.is_match(&text) != *negate,
+                            Test::Contains(value) => text.contains(value.as_str()),
+                            Test::In(cidr) => cidr.contains(&text),
+                            Test::Number(..) => unreachable!(),
+                        },
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// An address range, `10.0.0.0/8`, or a single address.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    fn parse(s: &str) -> Option<Cidr> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr.parse().ok()?, Some(prefix.parse().ok()?)),
+            None => (s.parse().ok()?, None),
+        };
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix = prefix.unwrap_or(bits);
+        (prefix <= bits).then_some(Cidr { addr, prefix })
+    }
+
+    fn contains(&self, text: &str) -> bool {
+        let text = text.trim_start_matches('[').trim_end_matches(']');
+        let mask = |bits: u32, width: u32| match bits {
+            0 => 0,
+            bits => u128::MAX << (width - bits),
+        };
+        match (self.addr, text.parse::<IpAddr>()) {
+            (IpAddr::V4(net), Ok(IpAddr::V4(ip))) => {
+                let mask = mask(self.prefix as u32, 32);
+                (u32::from(net) as u128 & mask) == (u32::from(ip) as u128 & mask)
+            }
+            (IpAddr::V6(net), Ok(IpAddr::V6(ip))) => {
+                let mask = mask(self.prefix as u32, 128);
+                (u128::from(net) & mask) == (u128::from(ip) & mask)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    And,
+    Or,
+    Not,
+    Op(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(word) => f.write_str(word),
+            Token::Str(s) => write!(f, "{:?}", s),
+            Token::LParen => f.write_str("("),
+            Token::RParen => f.write_str(")"),
+            Token::LBracket => f.write_str("["),
+            Token::RBracket => f.write_str("]"),
+            Token::And => f.write_str("and"),
+            Token::Or => f.write_str("or"),
+            Token::Not => f.write_str("not"),
+            Token::Op(op) => f.write_str(op),
+        }
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || "_-.:/@*".contains(c)
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    const SYMBOLS: [&str; 13] = [
+        "&&", "||", "==", "!=", "<=", ">=", "=~", "!~", "<", ">", "!", "=", "~",
+    ];
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(at, c)) = chars.peek() {
+        let rest = &input[at..];
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let (token, len) = match c {
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '[' => (Token::LBracket, 1),
+            ']' => (Token::RBracket, 1),
+            '"' | '\'' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(ExprError::new(at, ExprErrorKind::UnterminatedString)),
+                        Some((_, q)) if q == c => break,
+                        Some((_, '\\')) => match chars.next() {
+                            None => {
+                                return Err(ExprError::new(at, ExprErrorKind::UnterminatedString))
+                            }
+                            Some((_, 'n')) => value.push('\n'),
+                            Some((_, 't')) => value.push('\t'),
+                            // kept for regexes, e.g. "\."
+                            Some((_, e)) if e != c && e != '\\' => {
+                                value.push('\\');
+                                value.push(e);
+                            }
+                            Some((_, e)) => value.push(e),
+                        },
+                        Some((_, ch)) => value.push(ch),
+                    }
+                }
+                tokens.push((at, Token::Str(value)));
+                continue;
+            }
+            c if is_word(c) => {
+                let len = rest.find(|c| !is_word(c)).unwrap_or(rest.len());
+                let word = &rest[..len];
+                for _ in word.chars() {
+                    chars.next();
+                }
+                let token = match word.to_ascii_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "contains" => Token::Op("contains"),
+                    "in" => Token::Op("in"),
+                    _ => Token::Word(word.to_string()),
+                };
+                tokens.push((at, token));
+                continue;
+            }
+            _ => {
+                let symbol = SYMBOLS.iter().find(|s| rest.starts_with(*s));
+                let token = match symbol {
+                    Some(&"&&") => Token::And,
+                    Some(&"||") => Token::Or,
+                    Some(&"!") => Token::Not,
+                    Some(&"=") => Token::Op("=="),
+                    Some(&"~") | None => {
+                        return Err(ExprError::new(at, ExprErrorKind::UnexpectedChar(c)))
+                    }
+                    Some(op) => Token::Op(op),
+                };
+                (token, symbol.unwrap().len())
+            }
+        };
+        for _ in 0..len {
+            chars.next();
+        }
+        tokens.push((at, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens.get(self.pos).map(|(at, t)| (*at, t))
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), ExprError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| ExprError::new(self.end, ExprErrorKind::UnexpectedEnd))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek().is_some_and(|(_, t)| t == token);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, token: Token, expected: &'static str) -> Result<(), ExprError> {
+        let (at, found) = self.next()?;
+        if found == token {
+            Ok(())
+        } else {
+            Err(ExprError::new(
+                at,
+                ExprErrorKind::Expected {
+                    expected,
+                    found: found.to_string(),
+                },
+            ))
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.not()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, ExprError> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        let (at, token) = self.next()?;
+        let name = match token {
+            Token::LParen => {
+                let expr = self.or()?;
+                self.expect(Token::RParen, ")")?;
+                return Ok(expr);
+            }
+            Token::Word(name) => name,
+            found => {
+                return Err(ExprError::new(
+                    at,
+                    ExprErrorKind::Expected {
+                        expected: "字段或(",
+                        found: found.to_string(),
+                    },
+                ))
+            }
+        };
+        let operand = match name.to_ascii_lowercase().as_str() {
+            "true" => return Ok(Expr::Const(true)),
+            "false" => return Ok(Expr::Const(false)),
+            "host" => Operand::Field(Field::Hostname),
+            "app" => Operand::Field(Field::Appname),
+            "sd" if self.eat(&Token::LBracket) => {
+                let id = self.string()?;
+                self.expect(Token::RBracket, "]")?;
+                let param = if self.eat(&Token::LBracket) {
+                    let param = self.string()?;
+                    self.expect(Token::RBracket, "]")?;
+                    Some(param)
+                } else {
+                    None
+                };
+                Operand::Sd(id, param)
+            }
+            lower => Operand::Field(
+                lower
+                    .parse()
+                    .map_err(|e| ExprError::new(at, ExprErrorKind::UnknownName(e)))?,
+            ),
+        };
+        let (op_at, op) = match self.peek() {
+            Some((op_at, Token::Op(op))) => (op_at, *op),
+            _ => return Ok(Expr::Test(operand, Test::Exists)),
+        };
+        self.pos += 1;
+        let (value_at, value) = self.value()?;
+        let test = self.test(&operand, &name, op, op_at, &value, value_at)?;
+        Ok(Expr::Test(operand, test))
+    }
+
+    /// A quoted string or a bare word.
+    fn value(&mut self) -> Result<(usize, String), ExprError> {
+        match self.next()? {
+            (at, Token::Str(s) | Token::Word(s)) => Ok((at, s)),
+            (at, found) => Err(ExprError::new(
+                at,
+                ExprErrorKind::Expected {
+                    expected: "值",
+                    found: found.to_string(),
+                },
+            )),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ExprError> {
+        match self.next()? {
+            (_, Token::Str(s)) => Ok(s),
+            (at, found) => Err(ExprError::new(
+                at,
+                ExprErrorKind::Expected {
+                    expected: "带引号的字符串",
+                    found: found.to_string(),
+                },
+            )),
+        }
+    }
+
+    fn test(
+        &self,
+        operand: &Operand,
+        name: &str,
+        op: &'static str,
+        op_at: usize,
+        value: &str,
+        value_at: usize,
+    ) -> Result<Test, ExprError> {
+        let cmp = match op {
+            "==" => Some(Op::Eq),
+            "!=" => Some(Op::Ne),
+            "<" => Some(Op::Lt),
+            "<=" => Some(Op::Le),
+            ">" => Some(Op::Gt),
+            ">=" => Some(Op::Ge),
+            _ => None,
+        };
+        let error = |kind| ExprError::new(value_at, kind);
+        let test = match (op, cmp) {
+            (_, Some(cmp)) if operand.is_numeric() => {
+                let number = match operand {
+                    Operand::Field(Field::Facility) => Facility::from_str(value)
+                        .map(|f| f.code() as u32)
+                        .map_err(|e| error(e.into()))?,
+                    Operand::Field(Field::Severity) => Severity::from_str(value)
+                        .map(|s| s.code() as u32)
+                        .map_err(|e| error(e.into()))?,
+                    _ => value
+                        .parse()
+                        .map_err(|_| error(ExprErrorKind::Number(value.to_string())))?,
+                };
+                Test::Number(cmp, number)
+            }
+            (_, Some(cmp @ (Op::Eq | Op::Ne))) => Test::Text(cmp, value.to_string()),
+            ("=~" | "!~", _) => Test::Regex(
+                Regex::new(value).map_err(|e| error(ExprErrorKind::Regex(e.to_string())))?,
+                op == "!~",
+            ),
+            ("contains", _) => Test::Contains(value.to_string()),
+            ("in", _) => Test::In(
+                Cidr::parse(value).ok_or_else(|| error(ExprErrorKind::Cidr(value.to_string())))?,
+            ),
+            _ => {
+                return Err(ExprError::new(
+                    op_at,
+                    ExprErrorKind::Operator {
+                        field: name.to_string(),
+                        op,
+                    },
+                ))
+            }
+        };
+        Ok(test)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SSHD: &str =
+        r#"<36>1 2024-03-01T12:00:00Z web1 sshd 42 - [origin ip="10.1.2.3"] failed login"#;
+    const KERNEL: &str = "<6>Mar  1 12:00:00 192.168.1.20 kernel: eth0 up";
+
+    fn check(expr: &str, msg: &str) -> bool {
+        let filter = Filter::parse(expr).unwrap_or_else(|e| panic!("{}: {}", expr, e));
+        filter.matches(&Message::parse(msg).unwrap())
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert!(check("severity <= warning", SSHD));
+        assert!(!check("severity < warning", SSHD));
+        assert!(check("severity == warn", SSHD));
+        assert!(check("facility == auth and facility > daemon", SSHD));
+        assert!(check("pri >= 36 && version == 1", SSHD));
+        assert!(check(r#"app == "sshd" and host != web2"#, SSHD));
+        assert!(check("msg contains login", SSHD));
+        assert!(check(r#"msg =~ "^failed\s""#, SSHD));
+        assert!(check(r#"sd["origin"]["ip"] =~ "^10\.""#, SSHD));
+        assert!(check(r#"sd["origin"] and not sd["meta"]"#, SSHD));
+        assert!(check(r#"sd["origin"]["ip"] in 10.0.0.0/8"#, SSHD));
+        assert!(check("host in 192.168.0.0/16", KERNEL));
+        assert!(!check("host in 192.168.0.0/24", KERNEL));
+        assert!(!check("host in 10.0.0.0/8", SSHD));
+        assert!(check("protocol == rfc3164 and truncated == false", KERNEL));
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        // version and msgid are absent from RFC3164 messages
+        assert!(!check("version == 1", KERNEL));
+        assert!(check("version != 1", KERNEL));
+        assert!(!check("msgid", KERNEL));
+        assert!(check(r#"msgid !~ ".""#, KERNEL));
+        assert!(!check(r#"sd["origin"]["ip"] == x"#, KERNEL));
+    }
+
+    #[test]
+    fn test_boolean_operators() {
+        assert!(check("app == cron or severity <= err or app == sshd", SSHD));
+        assert!(!check(
+            "(app == cron or app == sshd) and severity > warning",
+            SSHD
+        ));
+        assert!(check("not (app == cron) AND !false", SSHD));
+        // and binds tighter than or
+        assert!(check("true or false and false", SSHD));
+    }
+
+    #[test]
+    fn test_cidr() {
+        let net = Cidr::parse("fe80::/10").unwrap();
+        assert!(net.contains("fe80::1"));
+        assert!(net.contains("[febf::1]"));
+        assert!(!net.contains("fec0::1"));
+        assert!(!net.contains("10.0.0.1"));
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains("8.8.8.8"));
+        assert!(Cidr::parse("10.0.0.1").unwrap().contains("10.0.0.1"));
+        assert_eq!(Cidr::parse("10.0.0.0/33"), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |expr: &str| Filter::parse(expr).unwrap_err();
+        assert_eq!(error("severity <= loud").position, 12);
+        assert_eq!(
+            error("severity <= loud").kind,
+            ExprErrorKind::UnknownName(crate::error::UnknownName::new("severity", "loud"))
+        );
+        assert_eq!(error("nope == 1").position, 0);
+        assert_eq!(error("app == sshd and").kind, ExprErrorKind::UnexpectedEnd);
+        assert_eq!(error("app == sshd and").position, 15);
+        assert_eq!(error("(app == sshd").kind, ExprErrorKind::UnexpectedEnd);
+        assert_eq!(
+            error("app == \"sshd").kind,
+            ExprErrorKind::UnterminatedString
+        );
+        assert_eq!(error("app < sshd").position, 4);
+        assert_eq!(error("msg =~ \"(\"").position, 7);
+        assert_eq!(error("host in 10.0.0.0/40").position, 8);
+        assert_eq!(
+            error("app == sshd $").kind,
+            ExprErrorKind::UnexpectedChar('$')
+        );
+        assert_eq!(error("app == sshd app").position, 12);
+        assert_eq!(
+            error("app == sshd and").pointer("app == sshd and"),
+            "app == sshd and\n               ^"
+        );
+    }
+}
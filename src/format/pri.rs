@@ -1,9 +1,11 @@
-use crate::error::{New, NomError, NomErrorKind};
+use crate::error::{New, NomError, NomErrorKind, UnknownName};
 use crate::format::digits;
 use nom::bytes::complete::tag;
-use nom::combinator::{map, opt};
+use nom::combinator::{map, map_opt, opt};
 use nom::sequence::delimited;
 use nom::IResult;
+use serde_derive::Serialize;
+use std::str::FromStr;
 
 // 0~191
 pub fn decompose_pri(pri: u8) -> Option<u8> {
@@ -21,25 +23,276 @@ pub fn decompose_pri(pri: u8) -> Option<u8> {
     Some(pri)
 }
 
+/// Facility part of the PRI, RFC5424 section 6.2.1.
+#[derive(Debug, Display, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Facility {
+    #[strum(serialize = "kern")]
+    Kern = 0,
+    #[strum(serialize = "user")]
+    User = 1,
+    #[strum(serialize = "mail")]
+    Mail = 2,
+    #[strum(serialize = "daemon")]
+    Daemon = 3,
+    #[strum(serialize = "auth")]
+    Auth = 4,
+    #[strum(serialize = "syslog")]
+    Syslog = 5,
+    #[strum(serialize = "lpr")]
+    Lpr = 6,
+    #[strum(serialize = "news")]
+    News = 7,
+    #[strum(serialize = "uucp")]
+    Uucp = 8,
+    #[strum(serialize = "cron")]
+    Cron = 9,
+    #[strum(serialize = "authpriv")]
+    Authpriv = 10,
+    #[strum(serialize = "ftp")]
+    Ftp = 11,
+    #[strum(serialize = "ntp")]
+    Ntp = 12,
+    #[strum(serialize = "security")]
+    Security = 13,
+    #[strum(serialize = "console")]
+    Console = 14,
+    #[strum(serialize = "solaris-cron")]
+    SolarisCron = 15,
+    #[strum(serialize = "local0")]
+    Local0 = 16,
+    #[strum(serialize = "local1")]
+    Local1 = 17,
+    #[strum(serialize = "local2")]
+    Local2 = 18,
+    #[strum(serialize = "local3")]
+    Local3 = 19,
+    #[strum(serialize = "local4")]
+    Local4 = 20,
+    #[strum(serialize = "local5")]
+    Local5 = 21,
+    #[strum(serialize = "local6")]
+    Local6 = 22,
+    #[strum(serialize = "local7")]
+    Local7 = 23,
+}
+
+impl Facility {
+    pub const ALL: [Facility; 24] = [
+        Facility::Kern,
+        Facility::User,
+        Facility::Mail,
+        Facility::Daemon,
+        Facility::Auth,
+        Facility::Syslog,
+        Facility::Lpr,
+        Facility::News,
+        Facility::Uucp,
+        Facility::Cron,
+        Facility::Authpriv,
+        Facility::Ftp,
+        Facility::Ntp,
+        Facility::Security,
+        Facility::Console,
+        Facility::SolarisCron,
+        Facility::Local0,
+        Facility::Local1,
+        Facility::Local2,
+        Facility::Local3,
+        Facility::Local4,
+        Facility::Local5,
+        Facility::Local6,
+        Facility::Local7,
+    ];
+
+    pub fn from_code(code: u8) -> Option<Facility> {
+        Facility::ALL.get(code as usize).copied()
+    }
+
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Accepts the names printed by `Display` or a numeric code.
+impl FromStr for Facility {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, UnknownName> {
+        let lower = s.to_ascii_lowercase();
+        Facility::ALL
+            .into_iter()
+            .find(|facility| facility.to_string() == lower)
+            .or_else(|| lower.parse().ok().and_then(Facility::from_code))
+            .ok_or_else(|| UnknownName::new("facility", s))
+    }
+}
+
+/// Severity part of the PRI, RFC5424 section 6.2.1. Lower is more severe.
+#[derive(Debug, Display, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[strum(serialize = "emerg")]
+    Emerg = 0,
+    #[strum(serialize = "alert")]
+    Alert = 1,
+    #[strum(serialize = "crit")]
+    Crit = 2,
+    #[strum(serialize = "err")]
+    Err = 3,
+    #[strum(serialize = "warning")]
+    Warning = 4,
+    #[strum(serialize = "notice")]
+    Notice = 5,
+    #[strum(serialize = "info")]
+    Info = 6,
+    #[strum(serialize = "debug")]
+    Debug = 7,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 8] = [
+        Severity::Emerg,
+        Severity::Alert,
+        Severity::Crit,
+        Severity::Err,
+        Severity::Warning,
+        Severity::Notice,
+        Severity::Info,
+        Severity::Debug,
+    ];
+
+    pub fn from_code(code: u8) -> Option<Severity> {
+        Severity::ALL.get(code as usize).copied()
+    }
+
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Accepts the names printed by `Display`, the deprecated syslog.conf
+/// spellings `panic`, `error` and `warn`, or a numeric code.
+impl FromStr for Severity {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, UnknownName> {
+        let lower = s.to_ascii_lowercase();
+        match lower.as_str() {
+            "panic" => return Ok(Severity::Emerg),
+            "error" => return Ok(Severity::Err),
+            "warn" => return Ok(Severity::Warning),
+            _ => {}
+        }
+        Severity::ALL
+            .into_iter()
+            .find(|severity| severity.to_string() == lower)
+            .or_else(|| lower.parse().ok().and_then(Severity::from_code))
+            .ok_or_else(|| UnknownName::new("severity", s))
+    }
+}
+
+/// Split a PRI value into its facility and severity.
+pub fn split_pri(pri: u8) -> Option<(Facility, Severity)> {
+    Some((
+        Facility::from_code(pri >> 3)?,
+        Severity::from_code(pri & 0x7)?,
+    ))
+}
+
+/// Cheap check whether `input`, starting at `<`, can begin a PRI, before
+/// running the full header parser. Input that ends before the `>` counts
+/// as possible.
+pub(crate) fn could_be_pri(input: &[u8]) -> bool {
+    let mut value = 0u16;
+    for (i, b) in input.iter().enumerate().skip(1).take(4) {
+        match b {
+            b'0'..=b'9' if i <= 3 => value = value * 10 + (b - b'0') as u16,
+            b'>' => return i > 1 && value <= 191,
+            _ => return false,
+        }
+    }
+    value <= 191
+}
+
+/// Parse `<PRI>` and return it as written, e.g. `<165>`.
+#[deprecated(note = "use `Message::parse` and `Message::pri`")]
+#[allow(dead_code)]
 pub fn pri(input: &str) -> IResult<&str, String> {
+    map(pri_value, |v| format!("<{}>", v))(input)
+}
+
+/// Parse `<PRI>` and return its numeric value.
+pub(crate) fn pri_value(input: &str) -> IResult<&str, u8> {
     match opt(delimited(tag("<"), map(digits, decompose_pri), tag(">")))(input) {
         Ok((res, v)) => match v {
-            Some(Some(v)) => Ok((res, format!("<{}>", v))),
+            Some(Some(v)) => Ok((res, v)),
             _ => Err(NomError::new(input, NomErrorKind::Fail)),
         },
         Err(e) => Err(e),
     }
 }
 
+/// Parse `<PRI>` into its facility and severity.
+pub(crate) fn pri_parts(input: &str) -> IResult<&str, (Facility, Severity)> {
+    map_opt(pri_value, split_pri)(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
+    #[allow(deprecated, clippy::bool_assert_comparison)]
     fn parse_pri() {
         assert_eq!(pri("<165>").unwrap(), ("", "<165>".to_string())); // 20 * 8 + 5
         assert_eq!(pri("<193>").is_err(), true); // 24 * 8 + 1
         assert_eq!(pri("<190>").unwrap(), ("", "<190>".to_string()));
         assert_eq!(pri("<0>").unwrap(), ("", "<0>".to_string()));
     }
+
+    #[test]
+    fn test_pri_value() {
+        assert_eq!(pri_value("<165>").unwrap(), ("", 165));
+        assert!(pri_value("<193>").is_err());
+        assert_eq!(pri_value("<0>").unwrap(), ("", 0));
+    }
+
+    #[test]
+    fn test_could_be_pri() {
+        assert!(could_be_pri(b"<165>1"));
+        assert!(could_be_pri(b"<0>"));
+        assert!(could_be_pri(b"<"));
+        assert!(could_be_pri(b"<19"));
+        assert!(!could_be_pri(b"<>"));
+        assert!(!could_be_pri(b"<192>"));
+        assert!(!could_be_pri(b"<1000>"));
+        assert!(!could_be_pri(b"<a>"));
+        assert!(!could_be_pri(b"<999"));
+    }
+
+    #[test]
+    fn test_split_pri() {
+        assert_eq!(split_pri(165), Some((Facility::Local4, Severity::Notice)));
+        assert_eq!(split_pri(0), Some((Facility::Kern, Severity::Emerg)));
+        assert_eq!(split_pri(191), Some((Facility::Local7, Severity::Debug)));
+        assert_eq!(split_pri(192), None);
+        assert_eq!(Facility::SolarisCron.to_string(), "solaris-cron");
+        assert_eq!(Severity::Warning.to_string(), "warning");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("solaris-cron".parse(), Ok(Facility::SolarisCron));
+        assert_eq!("LOCAL4".parse(), Ok(Facility::Local4));
+        assert_eq!("10".parse(), Ok(Facility::Authpriv));
+        assert!("24".parse::<Facility>().is_err());
+        assert_eq!("warn".parse(), Ok(Severity::Warning));
+        assert_eq!("err".parse(), Ok(Severity::Err));
+        assert_eq!("7".parse(), Ok(Severity::Debug));
+        assert_eq!(
+            "loud".parse::<Severity>(),
+            Err(UnknownName::new("severity", "loud"))
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use crate::message::SdElement;
 use nom::{
     branch::alt,
     bytes::complete::{escaped, tag, take_till1, take_until, take_while1},
@@ -7,9 +8,10 @@ use nom::{
     sequence::{delimited, separated_pair, terminated, tuple},
     IResult,
 };
+use std::fmt::{self, Display, Formatter};
 
 fn value0(input: &str) -> IResult<&str, String> {
-    match map(tag(r#""""#), |_| "".to_string())(input) {
+    match map(tag(r#""""#), |v: &str| v.to_string())(input) {
         Ok((res, v)) => Ok((res, v)),
         Err(e) => Err(e),
     }
@@ -36,48 +38,72 @@ fn param_value(input: &str) -> IResult<&str, String> {
 }
 
 /// Parse a param name="value"
-fn param(input: &str) -> IResult<&str, String> {
-    match separated_pair(
+fn param(input: &str) -> IResult<&str, (&str, String)> {
+    separated_pair(
         take_till1(|c: char| c == ']' || c == '='),
         terminated(tag("="), space0),
         param_value,
     )(input)
-    {
-        Ok((res, (k, v))) => Ok((res, format!("{}={}", k, v))),
-        Err(e) => Err(e),
+}
+
+/// Remove the `\` escapes from a quoted param value.
+fn unescape(value: &str) -> String {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
     }
+    unescaped
 }
 
 /// Parse a single structured data record.
 /// [exampleSDID@32473 iut="3" eventSource="Application" eventID="1011"]
-fn structured_datum_strict(input: &str) -> IResult<&str, String> {
+fn structured_datum_strict(input: &str) -> IResult<&str, (String, Option<SdElement>)> {
     match delimited(
         tag("["),
-        map(
-            tuple((
-                take_till1(|c: char| c.is_whitespace() || c == ']' || c == '='),
-                space0,
-                separated_list0(tag(" "), param),
-            )),
-            |(id, space, params)| format!("{}{}{}", id, space, params.join(" ")),
-        ),
+        tuple((
+            take_till1(|c: char| c.is_whitespace() || c == ']' || c == '='),
+            space0,
+            separated_list0(tag(" "), param),
+        )),
         tag("]"),
     )(input)
     {
-        Ok((res, v)) => Ok((res, format!("[{}]", v))),
+        Ok((res, (id, space, params))) => {
+            let raw = params
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let element = SdElement {
+                id: id.to_string(),
+                params: params
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), unescape(v)))
+                    .collect(),
+            };
+            Ok((res, (format!("[{}{}{}]", id, space, raw), Some(element))))
+        }
         Err(e) => Err(e),
     }
 }
 
-fn inner_permissive(input: &str) -> IResult<&str, String> {
+fn inner_permissive(input: &str) -> IResult<&str, (String, Option<SdElement>)> {
     match delimited(tag("["), map(take_until("]"), |v: &str| v), tag("]"))(input) {
-        Ok((res, v)) => Ok((res, format!("[{}]", v))),
+        Ok((res, v)) => Ok((res, (format!("[{}]", v), None))),
         Err(e) => Err(e),
     }
 }
 
 // /// Parse a single structured data record allowing anything between brackets.
-fn structured_datum_permissive(input: &str) -> IResult<&str, String> {
+fn structured_datum_permissive(input: &str) -> IResult<&str, (String, Option<SdElement>)> {
     alt((
         structured_datum_strict,
         // If the element fails to parse, just parse it and return None.
@@ -86,7 +112,9 @@ fn structured_datum_permissive(input: &str) -> IResult<&str, String> {
 }
 //
 // /// Parse a single structured data record.
-fn structured_datum(allow_failure: bool) -> impl FnMut(&str) -> IResult<&str, String> {
+fn structured_datum(
+    allow_failure: bool,
+) -> impl FnMut(&str) -> IResult<&str, (String, Option<SdElement>)> {
     if allow_failure {
         structured_datum_permissive
     } else {
@@ -94,22 +122,43 @@ fn structured_datum(allow_failure: bool) -> impl FnMut(&str) -> IResult<&str, St
     }
 }
 
+/// Structured data as it appeared in the header, and the elements that
+/// could be decoded from it.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub(crate) struct StructuredData {
+    pub raw: String,
+    pub elements: Vec<SdElement>,
+}
+
+impl Display for StructuredData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
 /// Parse multiple structured data elements.
 pub(crate) fn structured_data_optional(
     allow_failure: bool,
-) -> impl FnMut(&str) -> IResult<&str, String> {
+) -> impl FnMut(&str) -> IResult<&str, StructuredData> {
     move |input| {
         alt((
-            map(tag("-"), |_| "-".to_string()),
+            map(tag("-"), |_| StructuredData {
+                raw: "-".to_string(),
+                elements: vec![],
+            }),
             map(many1(structured_datum(allow_failure)), |items| {
-                items.join("")
+                let (raw, elements): (Vec<_>, Vec<_>) = items.into_iter().unzip();
+                StructuredData {
+                    raw: raw.join(""),
+                    elements: elements.into_iter().flatten().collect(),
+                }
             }),
         ))(input)
     }
 }
 
 /// Parse multiple structured data elements.
-pub(crate) fn structured_data(input: &str) -> IResult<&str, String> {
+pub(crate) fn structured_data(input: &str) -> IResult<&str, StructuredData> {
     structured_data_optional(true)(input)
 }
 
@@ -121,10 +170,45 @@ mod tests {
     fn test_structured_data() {
         let data = r#"[exampleSDID@32473 iut="3" eventSource="Application" eventID="1011"]"#;
         let value = structured_data(data).unwrap();
-        assert_eq!(data, value.1);
+        assert_eq!(data, value.1.raw);
 
         let data = r#"[exampleSDID@32473 iut="3" eventSource="Application" eventID="1011"][examplePriority@32473 class="high"]"#;
         let value = structured_data(data).unwrap();
-        assert_eq!(data, value.1);
+        assert_eq!(data, value.1.raw);
+        assert_eq!(value.1.elements.len(), 2);
+        assert_eq!(value.1.elements[1].id, "examplePriority@32473");
+        assert_eq!(
+            value.1.elements[1].params,
+            vec![("class".to_string(), "high".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_structured_data_escaped() {
+        let data = r#"[id@1 a="x\"y" b="\\" c=""][broken"#;
+        let value = structured_data(data).unwrap();
+        assert_eq!(value.0, "[broken");
+        assert_eq!(
+            value.1.elements[0].params,
+            vec![
+                ("a".to_string(), "x\"y".to_string()),
+                ("b".to_string(), "\\".to_string()),
+                ("c".to_string(), "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_structured_data_empty_value() {
+        // The raw text keeps the quotes of an empty value, as for any
+        // other value, so the header length matches the input.
+        let data = r#"[id@1 c="" d="x"] msg"#;
+        let value = structured_data(data).unwrap();
+        assert_eq!(value.0, " msg");
+        assert_eq!(value.1.raw, r#"[id@1 c="" d="x"]"#);
+        assert_eq!(
+            value.1.elements[0].params[0],
+            ("c".to_string(), "".to_string())
+        );
     }
 }
@@ -6,7 +6,7 @@ use nom::combinator::{map, map_res, opt};
 use nom::sequence::tuple;
 use nom::IResult;
 
-fn parse_timestamp(input: &str) -> ParseResult<&str> {
+fn parse_timestamp(input: &str) -> ParseResult<'_, &str> {
     if chrono::DateTime::parse_from_rfc3339(input).is_ok() {
         Ok(input)
     } else {
@@ -95,9 +95,9 @@ fn timestamp_3164_with_year(input: &str) -> IResult<&str, String> {
 /// # Arguments
 ///
 /// * get_year - a function that is called if the parsed message contains a date with no year.
-///              the function takes a (month, date, hour, minute, second) tuple and should return the year to use.
+///   the function takes a (month, date, hour, minute, second) tuple and should return the year to use.
 /// * tz - An optional timezone.
-///        If None is specified and the parsed date doesn't specify a timezone the date is parsed in time local time.
+///   If None is specified and the parsed date doesn't specify a timezone the date is parsed in time local time.
 ///
 pub(crate) fn timestamp_3164(input: &str) -> IResult<&str, String> {
     alt((
@@ -0,0 +1,511 @@
+use crate::error::{ErrorKind, UnknownName};
+use crate::message::Message;
+use crate::parser::StreamParser;
+use crate::stream::MessageResult;
+use memchr::memchr;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Longest frame accepted by default.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 64 * 1024;
+
+/// Longest MSG-LEN, in digits.
+const MAX_LEN_DIGITS: usize = 10;
+
+/// How messages are delimited on a stream transport.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Framing {
+    /// No framing: messages are split where a new header starts, as
+    /// `StreamParser` does.
+    #[default]
+    Heuristic,
+    /// `MSG-LEN SP SYSLOG-MSG`, RFC6587 section 3.4.1 and RFC5425.
+    OctetCounting,
+    /// Each message is followed by a trailer byte, RFC6587 section 3.4.2.
+    /// A `\r` before a `\n` trailer is dropped.
+    NonTransparent(u8),
+}
+
+impl Framing {
+    /// Non-transparent framing with the usual LF trailer.
+    pub const LF: Framing = Framing::NonTransparent(b'\n');
+
+    /// Write one framed message.
+    pub fn encode<W: Write>(&self, msg: &str, out: &mut W) -> io::Result<()> {
+        self.encode_bytes(msg.as_bytes(), out)
+    }
+
+    /// Write one framed message that need not be UTF-8.
+    pub fn encode_bytes<W: Write>(&self, msg: &[u8], out: &mut W) -> io::Result<()> {
+        match *self {
+            Framing::Heuristic => out.write_all(msg),
+            Framing::OctetCounting => {
+                write!(out, "{} ", msg.len())?;
+                out.write_all(msg)
+            }
+            Framing::NonTransparent(trailer) => {
+                out.write_all(msg)?;
+                out.write_all(&[trailer])
+            }
+        }
+    }
+}
+
+/// `heuristic`, `octet-counting`, `lf` or `nul`.
+impl FromStr for Framing {
+    type Err = UnknownName;
+
+    fn from_str(s: &str) -> Result<Self, UnknownName> {
+        match s {
+            "heuristic" => Ok(Framing::Heuristic),
+            "octet-counting" => Ok(Framing::OctetCounting),
+            "lf" => Ok(Framing::LF),
+            "nul" => Ok(Framing::NonTransparent(0)),
+            _ => Err(UnknownName::new("framing", s)),
+        }
+    }
+}
+
+impl Display for Framing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Framing::Heuristic => f.write_str("heuristic"),
+            Framing::OctetCounting => f.write_str("octet-counting"),
+            Framing::NonTransparent(b'\n') => f.write_str("lf"),
+            Framing::NonTransparent(0) => f.write_str("nul"),
+            Framing::NonTransparent(trailer) => write!(f, "trailer {:#04x}", trailer),
+        }
+    }
+}
+
+/// Cuts a byte stream into messages according to a `Framing`.
+///
+/// Heuristic framing is delegated to `StreamParser`. A frame longer than the
+/// limit is reported as `ErrorKind::TooLarge` and skipped, and a malformed
+/// octet count as `ErrorKind::Framing`, after which the input buffered so
+/// far is dropped since the stream cannot be resynced.
+#[derive(Debug)]
+pub struct FrameDecoder {
+    framing: Framing,
+    parser: StreamParser,
+    buf: Vec<u8>,
+    /// Stream offset of `buf[0]`.
+    base: u64,
+    start: usize,
+    /// Stream offset of the last message returned.
+    last_offset: u64,
+    /// Bytes of the last message returned.
+    last_frame: Vec<u8>,
+    /// What is left of an oversized frame.
+    skip: Skip,
+    max_frame_len: usize,
+    eof: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Skip {
+    None,
+    Bytes(usize),
+    ToTrailer,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        FrameDecoder::new(Framing::default())
+    }
+}
+
+impl FrameDecoder {
+    pub fn new(framing: Framing) -> Self {
+        FrameDecoder {
+            framing,
+            parser: StreamParser::new(),
+            buf: Vec::new(),
+            base: 0,
+            start: 0,
+            last_offset: 0,
+            last_frame: Vec::new(),
+            skip: Skip::None,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            eof: false,
+        }
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Longest frame, 64 KiB by default.
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.max_frame_len = len;
+        self.parser.set_max_frame_len(len);
+    }
+
+    /// Append a chunk of input.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        if self.framing == Framing::Heuristic {
+            self.parser.feed(chunk);
+            return;
+        }
+        if self.start > 0 && self.start >= self.buf.len() / 2 {
+            self.buf.drain(..self.start);
+            self.base += self.start as u64;
+            self.start = 0;
+        }
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Mark the end of input, so that the last unterminated frame is emitted.
+    pub fn finish(&mut self) {
+        self.eof = true;
+        self.parser.finish();
+    }
+
+    /// Bytes held for frames not yet complete.
+    pub fn pending(&self) -> usize {
+        match self.framing {
+            Framing::Heuristic => self.parser.pending(),
+            _ => self.buf.len() - self.start,
+        }
+    }
+
+    pub fn next_message(&mut self) -> Option<MessageResult> {
+        match self.framing {
+            Framing::Heuristic => self.parser.next_frame().map(|frame| {
+                self.last_offset = frame.offset;
+                self.last_frame.clear();
+                self.last_frame.extend_from_slice(frame.bytes());
+                frame.into_message()
+            }),
+            Framing::OctetCounting => self.next_counted(),
+            Framing::NonTransparent(trailer) => self.next_delimited(trailer),
+        }
+    }
+
+    /// Where the last message returned by `next_message` starts in the
+    /// stream, in bytes; for octet counting, where its MSG-LEN starts.
+    pub fn last_offset(&self) -> u64 {
+        self.last_offset
+    }
+
+    /// The bytes the last message returned was parsed from, without its
+    /// framing; for an oversized one, the part that was kept.
+    pub fn last_frame(&self) -> &[u8] {
+        &self.last_frame
+    }
+
+    fn next_counted(&mut self) -> Option<MessageResult> {
+        loop {
+            if let Skip::Bytes(n) = self.skip {
+                let skipped = n.min(self.buf.len() - self.start);
+                self.start += skipped;
+                if skipped < n {
+                    self.skip = Skip::Bytes(n - skipped);
+                    return None;
+                }
+                self.skip = Skip::None;
+            }
+            // tolerate line breaks between frames
+            while self.start < self.buf.len() && self.buf[self.start].is_ascii_whitespace() {
+                self.start += 1;
+            }
+            let offset = self.base + self.start as u64;
+            let input = &self.buf[self.start..];
+            if input.is_empty() {
+                return None;
+            }
+            let digits = input.iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits == input.len() && digits <= MAX_LEN_DIGITS && !self.eof {
+                return None;
+            }
+            let len = match input.get(digits) {
+                Some(b' ') if digits > 0 && digits <= MAX_LEN_DIGITS => {
+                    std::str::from_utf8(&input[..digits])
+                        .ok()
+                        .and_then(|digits| digits.parse::<usize>().ok())
+                }
+                _ => None,
+            };
+            let Some(len) = len else {
+                let raw = String::from_utf8_lossy(input).into_owned();
+                let msg = self.emit(
+                    offset,
+                    self.start..self.buf.len(),
+                    Err((raw, ErrorKind::Framing)),
+                );
+                self.buf.clear();
+                self.start = 0;
+                return msg;
+            };
+            let body = self.start + digits + 1;
+            if len > self.max_frame_len {
+                // report the start of the frame, then skip the rest
+                let shown = self.max_frame_len.min(self.buf.len() - body);
+                if shown < self.max_frame_len && !self.eof {
+                    return None;
+                }
+                let raw = String::from_utf8_lossy(&self.buf[body..body + shown]).into_owned();
+                self.start = body + shown;
+                self.skip = Skip::Bytes(len - shown);
+                return self.emit(offset, body..body + shown, Err((raw, ErrorKind::TooLarge)));
+            }
+            if self.buf.len() - body < len {
+                if self.eof && self.buf.len() > body {
+                    let frame = parse(&self.buf[body..]);
+                    self.start = self.buf.len();
+                    return self.emit(offset, body..self.buf.len(), frame);
+                }
+                return None;
+            }
+            self.start = body + len;
+            if len > 0 {
+                let frame = parse(&self.buf[body..body + len]);
+                return self.emit(offset, body..body + len, frame);
+            }
+        }
+    }
+
+    fn next_delimited(&mut self, trailer: u8) -> Option<MessageResult> {
+        loop {
+            let from = self.start;
+            let offset = self.base + from as u64;
+            let input = &self.buf[from..];
+            let Some(end) = memchr(trailer, input) else {
+                if self.skip == Skip::ToTrailer {
+                    self.start = self.buf.len();
+                    return None;
+                }
+                if input.len() > self.max_frame_len {
+                    let raw = String::from_utf8_lossy(&input[..self.max_frame_len]).into_owned();
+                    self.start = self.buf.len();
+                    self.skip = Skip::ToTrailer;
+                    let kept = from..from + self.max_frame_len;
+                    return self.emit(offset, kept, Err((raw, ErrorKind::TooLarge)));
+                }
+                if self.eof && !input.is_empty() {
+                    let frame = parse(input);
+                    self.start = self.buf.len();
+                    return self.emit(offset, from..self.buf.len(), frame);
+                }
+                return None;
+            };
+            let mut frame = &input[..end];
+            self.start += end + 1;
+            if self.skip == Skip::ToTrailer {
+                self.skip = Skip::None;
+                continue;
+            }
+            if trailer == b'\n' {
+                frame = frame.strip_suffix(b"\r").unwrap_or(frame);
+            }
+            let len = frame.len();
+            if len > self.max_frame_len {
+                let raw = String::from_utf8_lossy(&frame[..self.max_frame_len]).into_owned();
+                let kept = from..from + self.max_frame_len;
+                return self.emit(offset, kept, Err((raw, ErrorKind::TooLarge)));
+            }
+            if len > 0 {
+                let frame = parse(frame);
+                return self.emit(offset, from..from + len, frame);
+            }
+        }
+    }
+
+    fn emit(
+        &mut self,
+        offset: u64,
+        frame: Range<usize>,
+        msg: MessageResult,
+    ) -> Option<MessageResult> {
+        self.last_offset = offset;
+        self.last_frame.clear();
+        self.last_frame.extend_from_slice(&self.buf[frame]);
+        Some(msg)
+    }
+}
+
+fn parse(frame: &[u8]) -> MessageResult {
+    let raw = String::from_utf8_lossy(frame);
+    Message::parse(&raw).map_err(|kind| (raw.into_owned(), kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MSG1: &str =
+        "<34>1 2003-10-11T22:14:15.003Z mymachine.example.com su - ID47 - 'su root' failed";
+    const MSG2: &str = "<13>Oct 11 22:14:15 myhost app[7]: line\nwith newline";
+
+    fn decode(framing: Framing, input: &[u8], chunk: usize) -> Vec<MessageResult> {
+        let mut decoder = FrameDecoder::new(framing);
+        decoder.set_max_frame_len(100);
+        let mut out = Vec::new();
+        for chunk in input.chunks(chunk) {
+            decoder.feed(chunk);
+            out.extend(std::iter::from_fn(|| decoder.next_message()));
+        }
+        decoder.finish();
+        out.extend(std::iter::from_fn(|| decoder.next_message()));
+        out
+    }
+
+    fn texts(results: &[MessageResult]) -> Vec<String> {
+        results
+            .iter()
+            .map(|r| match r {
+                Ok(msg) => msg.to_string(),
+                Err((raw, kind)) => format!("{:?}: {}", kind, raw),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_octet_counting() {
+        let mut input = Vec::new();
+        for msg in [MSG1, MSG2] {
+            Framing::OctetCounting.encode(msg, &mut input).unwrap();
+        }
+        input.extend_from_slice(b"\n200 ");
+        input.extend_from_slice(&[b'x'; 200]);
+        Framing::OctetCounting.encode(MSG1, &mut input).unwrap();
+        input.extend_from_slice(b"12x");
+
+        for chunk in [1, 3, 64, input.len()] {
+            let out = decode(Framing::OctetCounting, &input, chunk);
+            assert_eq!(
+                texts(&out),
+                vec![
+                    MSG1.to_string(),
+                    MSG2.to_string(),
+                    format!("TooLarge: {}", "x".repeat(100)),
+                    MSG1.to_string(),
+                    "Framing: 12x".to_string(),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_octet_counting_incomplete() {
+        let out = decode(
+            Framing::OctetCounting,
+            format!("100 {}", MSG1).as_bytes(),
+            5,
+        );
+        assert_eq!(texts(&out), vec![MSG1.to_string()]);
+        let out = decode(Framing::OctetCounting, b"12", 1);
+        assert_eq!(texts(&out), vec!["Framing: 12".to_string()]);
+    }
+
+    #[test]
+    fn test_non_transparent() {
+        let msg2 = MSG2.replace('\n', " ");
+        let input = format!("{}\r\n\n{}\n{}\n{}", MSG1, msg2, "y".repeat(150), MSG1);
+        for chunk in [1, 7, input.len()] {
+            let out = decode(Framing::LF, input.as_bytes(), chunk);
+            assert_eq!(
+                texts(&out),
+                vec![
+                    MSG1.to_string(),
+                    msg2.clone(),
+                    format!("TooLarge: {}", "y".repeat(100)),
+                    MSG1.to_string(),
+                ]
+            );
+        }
+
+        let out = decode(
+            Framing::NonTransparent(0),
+            b"<13>Oct 11 22:14:15 app: a\nb\0garbage\0",
+            4,
+        );
+        assert_eq!(
+            texts(&out),
+            vec![
+                "<13>Oct 11 22:14:15 app: a\nb".to_string(),
+                "Header: garbage".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        for framing in [
+            Framing::Heuristic,
+            Framing::OctetCounting,
+            Framing::LF,
+            Framing::NonTransparent(0),
+        ] {
+            assert_eq!(framing.to_string().parse(), Ok(framing));
+        }
+        assert_eq!(
+            "crlf".parse::<Framing>(),
+            Err(UnknownName::new("framing", "crlf"))
+        );
+    }
+
+    #[test]
+    fn test_offsets() {
+        let offsets = |framing: Framing, input: &str| {
+            let mut decoder = FrameDecoder::new(framing);
+            let mut offsets = Vec::new();
+            for b in input.as_bytes() {
+                decoder.feed(&[*b]);
+                while decoder.next_message().is_some() {
+                    offsets.push(decoder.last_offset());
+                }
+            }
+            offsets
+        };
+        assert_eq!(offsets(Framing::LF, "a\n\nbc\r\nd\n"), vec![0, 3, 7]);
+        assert_eq!(
+            offsets(Framing::OctetCounting, "1 a\n2 bc3 def"),
+            vec![0, 4, 8]
+        );
+    }
+
+    #[test]
+    fn test_last_frame() {
+        let frames = |framing: Framing, input: &[u8]| {
+            let mut decoder = FrameDecoder::new(framing);
+            decoder.set_max_frame_len(4);
+            decoder.feed(input);
+            decoder.finish();
+            let mut frames = Vec::new();
+            while decoder.next_message().is_some() {
+                frames.push(decoder.last_frame().to_vec());
+            }
+            frames
+        };
+        assert_eq!(
+            frames(Framing::LF, b"a\xff\r\nbcdefg\nh"),
+            vec![b"a\xff".to_vec(), b"bcde".to_vec(), b"h".to_vec()]
+        );
+        assert_eq!(
+            frames(Framing::OctetCounting, b"2 ab6 cdefgh1 i"),
+            vec![b"ab".to_vec(), b"cdef".to_vec(), b"i".to_vec()]
+        );
+        let input = format!("{}{}", MSG1, MSG2);
+        let mut decoder = FrameDecoder::new(Framing::Heuristic);
+        decoder.feed(input.as_bytes());
+        decoder.finish();
+        decoder.next_message();
+        assert_eq!(decoder.last_frame(), MSG1.as_bytes());
+    }
+
+    #[test]
+    fn test_heuristic() {
+        let input = format!("{}{}", MSG1, MSG2);
+        let out = decode(Framing::Heuristic, input.as_bytes(), 3);
+        assert_eq!(texts(&out), vec![MSG1.to_string(), MSG2.to_string()]);
+
+        let input = format!("{}{}{}", MSG1, "x".repeat(1000), MSG2);
+        let out = decode(Framing::Heuristic, input.as_bytes(), 7);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0], Err((input[..100].to_string(), ErrorKind::TooLarge)));
+        assert_eq!(texts(&out[1..]), vec![MSG2.to_string()]);
+    }
+}
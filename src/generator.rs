@@ -0,0 +1,469 @@
+//! Synthetic messages for load and robustness tests.
+//!
+//! ```
+//! use syslog_parse::generator::{Generated, Generator};
+//! use syslog_parse::message::Severity;
+//!
+//! let mut generator = Generator::new(42);
+//! generator
+//!     .set_severities(vec![(Severity::Info, 9), (Severity::Err, 1)])
+//!     .unwrap();
+//! generator.set_malformed_ratio(0.1);
+//! for generated in generator.take(3) {
+//!     if let Generated::Valid(msg) = &generated {
+//!         assert!(msg.header.severity <= Severity::Info);
+//!     }
+//!     println!("{}", generated);
+//! }
+//! ```
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::GeneratorError;
+use crate::message::{Facility, Header, Message, Protocol, SdElement, Severity};
+use chrono::SecondsFormat;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+
+const WORDS: &[&str] = &[
+    "connection",
+    "from",
+    "accepted",
+    "closed",
+    "user",
+    "session",
+    "opened",
+    "request",
+    "timeout",
+    "retry",
+    "disk",
+    "queue",
+    "worker",
+    "started",
+    "stopped",
+    "failed",
+    "ok",
+];
+
+const MSGIDS: &[&str] = &["ID47", "AUDIT", "REQ", "CONN"];
+
+/// SplitMix64. Small, fast and reproducible; not for anything secret.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`, `n` > 0.
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+/// Values with relative weights.
+#[derive(Debug, Clone)]
+struct Weighted<T> {
+    items: Vec<(T, u32)>,
+    total: u64,
+}
+
+impl<T> Weighted<T> {
+    fn new(items: Vec<(T, u32)>) -> Result<Self, GeneratorError> {
+        let total = items.iter().map(|(_, w)| *w as u64).sum();
+        if total == 0 {
+            return Err(GeneratorError::ZeroWeights);
+        }
+        Ok(Weighted { items, total })
+    }
+
+    fn pick(&self, rng: &mut Rng) -> &T {
+        let mut n = rng.below(self.total);
+        for (item, weight) in &self.items {
+            if n < *weight as u64 {
+                return item;
+            }
+            n -= *weight as u64;
+        }
+        unreachable!()
+    }
+}
+
+/// Printable US-ASCII up to `max` characters, not the NILVALUE, and none
+/// of `reserved`, so the parser reads the field back.
+fn header_field(
+    field: &'static str,
+    value: &str,
+    max: usize,
+    reserved: &str,
+) -> Result<(), GeneratorError> {
+    if value.is_empty() || value == "-" || value.len() > max {
+        return Err(GeneratorError::Length {
+            field,
+            value: value.to_string(),
+            max,
+        });
+    }
+    match value
+        .chars()
+        .find(|c| !matches!(*c as u32, 33..=126) || reserved.contains(*c))
+    {
+        Some(found) => Err(GeneratorError::Char {
+            field,
+            value: value.to_string(),
+            found,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// One generated message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generated {
+    Valid(Message),
+    /// Text that no parser should accept as a message.
+    Malformed(String),
+}
+
+impl Display for Generated {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Generated::Valid(msg) => msg.fmt(f),
+            Generated::Malformed(text) => f.write_str(text),
+        }
+    }
+}
+
+/// An endless, seeded source of RFC5424 and RFC3164 messages.
+///
+/// The same seed and settings give the same messages, except for the
+/// timestamps, which come from the clock. Weights passed to the setters
+/// are relative; the setters reject ones that are all zero.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    rng: Rng,
+    rfc3164_ratio: f64,
+    facilities: Weighted<Facility>,
+    severities: Weighted<Severity>,
+    hosts: Weighted<String>,
+    apps: Weighted<String>,
+    sd_ratio: f64,
+    body_size: (usize, usize),
+    malformed_ratio: f64,
+    seq: u64,
+    clock: Arc<dyn Clock>,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        use Severity::*;
+        Generator {
+            rng: Rng(seed),
+            rfc3164_ratio: 0.0,
+            facilities: Weighted::new(vec![
+                (Facility::User, 4),
+                (Facility::Daemon, 2),
+                (Facility::Auth, 1),
+                (Facility::Local0, 1),
+            ])
+            .unwrap(),
+            severities: Weighted::new(vec![
+                (Err, 1),
+                (Warning, 2),
+                (Notice, 2),
+                (Info, 10),
+                (Debug, 3),
+            ])
+            .unwrap(),
+            hosts: Weighted::new(vec![("localhost".to_string(), 1)]).unwrap(),
+            apps: Weighted::new(vec![("dygen".to_string(), 1)]).unwrap(),
+            sd_ratio: 0.5,
+            body_size: (10, 80),
+            malformed_ratio: 0.0,
+            seq: 0,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Share of RFC3164 messages, 0 by default.
+    pub fn set_rfc3164_ratio(&mut self, ratio: f64) {
+        self.rfc3164_ratio = ratio;
+    }
+
+    pub fn set_facilities(&mut self, weights: Vec<(Facility, u32)>) -> Result<(), GeneratorError> {
+        self.facilities = Weighted::new(weights)?;
+        Ok(())
+    }
+
+    pub fn set_severities(&mut self, weights: Vec<(Severity, u32)>) -> Result<(), GeneratorError> {
+        self.severities = Weighted::new(weights)?;
+        Ok(())
+    }
+
+    /// HOSTNAMEs, without spaces or `:`.
+    pub fn set_hosts(&mut self, weights: Vec<(String, u32)>) -> Result<(), GeneratorError> {
+        for (host, _) in &weights {
+            header_field("HOSTNAME", host, 255, ":")?;
+        }
+        self.hosts = Weighted::new(weights)?;
+        Ok(())
+    }
+
+    /// APP-NAMEs, which also have to fit an RFC3164 TAG.
+    pub fn set_apps(&mut self, weights: Vec<(String, u32)>) -> Result<(), GeneratorError> {
+        for (app, _) in &weights {
+            header_field("APP-NAME", app, 48, ":[]")?;
+        }
+        self.apps = Weighted::new(weights)?;
+        Ok(())
+    }
+
+    /// Share of RFC5424 messages with structured data, 0.5 by default.
+    pub fn set_sd_ratio(&mut self, ratio: f64) {
+        self.sd_ratio = ratio;
+    }
+
+    /// Range of MSG lengths in bytes, 10 to 80 by default.
+    pub fn set_body_size(&mut self, min: usize, max: usize) {
+        self.body_size = (min, max.max(min));
+    }
+
+    /// Share of malformed messages, 0 by default.
+    pub fn set_malformed_ratio(&mut self, ratio: f64) {
+        self.malformed_ratio = ratio;
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn generate(&mut self) -> Generated {
+        let msg = self.message();
+        if self.rng.chance(self.malformed_ratio) {
+            Generated::Malformed(self.malform(&msg))
+        } else {
+            Generated::Valid(msg)
+        }
+    }
+
+    fn message(&mut self) -> Message {
+        self.seq += 1;
+        let now = self.clock.now();
+        let rng = &mut self.rng;
+        let rfc3164 = rng.chance(self.rfc3164_ratio);
+        let facility = *self.facilities.pick(rng);
+        let severity = *self.severities.pick(rng);
+        let hostname = self.hosts.pick(rng).clone();
+        let appname = self.apps.pick(rng).clone();
+        let procid = (1 + rng.below(65535)).to_string();
+        let (protocol, version, timestamp, msgid) = if rfc3164 {
+            let timestamp = now.format("%b %e %H:%M:%S").to_string();
+            (Protocol::Rfc3164, None, timestamp, None)
+        } else {
+            let timestamp = now.to_rfc3339_opts(SecondsFormat::Micros, false);
+            let msgid = (*rng.pick(MSGIDS)).to_string();
+            (Protocol::Rfc5424, Some(1), timestamp, Some(msgid))
+        };
+        let mut structured_data = Vec::new();
+        if !rfc3164 && rng.chance(self.sd_ratio) {
+            structured_data.push(SdElement {
+                id: "meta".to_string(),
+                params: vec![("sequenceId".to_string(), self.seq.to_string())],
+            });
+            if rng.chance(0.5) {
+                let ip = format!(
+                    "10.{}.{}.{}",
+                    rng.below(256),
+                    rng.below(256),
+                    rng.below(256)
+                );
+                structured_data.push(SdElement {
+                    id: "origin".to_string(),
+                    params: vec![("ip".to_string(), ip)],
+                });
+            }
+        }
+        let (min, max) = self.body_size;
+        let len = min + rng.below((max - min) as u64 + 1) as usize;
+        let mut msg = String::with_capacity(len + 8);
+        while msg.len() < len {
+            if !msg.is_empty() {
+                msg.push(' ');
+            }
+            msg.push_str(rng.pick::<&str>(WORDS));
+        }
+        msg.truncate(len);
+        // the parsers drop trailing spaces
+        while msg.ends_with(' ') {
+            msg.pop();
+            msg.push('x');
+        }
+        Message {
+            header: Header {
+                protocol,
+                facility,
+                severity,
+                version,
+                timestamp,
+                hostname: Some(hostname),
+                appname: Some(appname),
+                procid: Some(procid),
+                msgid,
+                structured_data,
+            },
+            msg,
+            truncated: false,
+        }
+    }
+
+    /// Break `msg` in one of a few ways that parsers must reject.
+    fn malform(&mut self, msg: &Message) -> String {
+        let text = msg.to_string();
+        let header = text.find('>').unwrap() + 1;
+        match self.rng.below(4) {
+            // PRI out of range
+            0 => format!("<{}>{}", 192 + self.rng.below(800), &text[header..]),
+            // no PRI at all
+            1 => text[header..].to_string(),
+            // cut inside the timestamp
+            2 => text[..header + 6].to_string(),
+            // binary noise
+            _ => (0..16 + self.rng.below(48))
+                .map(|_| (0x80 + self.rng.below(0x80) as u8) as char)
+                .collect(),
+        }
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Generated;
+
+    fn next(&mut self) -> Option<Generated> {
+        Some(self.generate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use chrono::{Local, TimeZone};
+
+    fn generator(seed: u64) -> Generator {
+        let mut generator = Generator::new(seed);
+        let now = Local.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        generator.set_clock(Arc::new(ManualClock::new(now)));
+        generator
+    }
+
+    #[test]
+    fn test_seed() {
+        let a: Vec<_> = generator(7).take(50).collect();
+        let b: Vec<_> = generator(7).take(50).collect();
+        let c: Vec<_> = generator(8).take(50).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_valid_messages_parse() {
+        let mut generator = generator(1);
+        generator.set_rfc3164_ratio(0.5);
+        generator.set_body_size(0, 200);
+        for generated in generator.take(500) {
+            let Generated::Valid(msg) = generated else {
+                panic!("malformed without a ratio");
+            };
+            assert_eq!(Message::parse(&msg.to_string()), Ok(msg));
+        }
+    }
+
+    #[test]
+    fn test_malformed_messages_fail() {
+        let mut generator = generator(2);
+        generator.set_rfc3164_ratio(0.5);
+        generator.set_malformed_ratio(0.25);
+        let mut malformed = 0;
+        for generated in generator.take(1000) {
+            if let Generated::Malformed(text) = &generated {
+                malformed += 1;
+                assert!(Message::parse(text).is_err(), "{:?}", text);
+            }
+        }
+        assert!((200..300).contains(&malformed), "{}", malformed);
+    }
+
+    #[test]
+    fn test_distributions() {
+        let mut generator = generator(3);
+        generator.set_facilities(vec![(Facility::Auth, 1)]).unwrap();
+        generator
+            .set_severities(vec![(Severity::Err, 1), (Severity::Info, 3)])
+            .unwrap();
+        generator
+            .set_hosts(vec![("a".to_string(), 1), ("b".to_string(), 0)])
+            .unwrap();
+        generator.set_sd_ratio(0.0);
+        generator.set_body_size(5, 5);
+        let msgs: Vec<_> = generator
+            .take(1000)
+            .map(|g| match g {
+                Generated::Valid(msg) => msg,
+                Generated::Malformed(_) => unreachable!(),
+            })
+            .collect();
+        assert!(msgs.iter().all(|m| m.header.facility == Facility::Auth
+            && m.header.hostname.as_deref() == Some("a")
+            && m.header.structured_data.is_empty()
+            && m.msg.len() == 5));
+        let errors = msgs
+            .iter()
+            .filter(|m| m.header.severity == Severity::Err)
+            .count();
+        assert!((200..300).contains(&errors), "{}", errors);
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let mut generator = generator(4);
+        assert_eq!(
+            generator.set_facilities(vec![(Facility::Auth, 0)]),
+            Err(GeneratorError::ZeroWeights)
+        );
+        assert_eq!(
+            generator.set_severities(Vec::new()),
+            Err(GeneratorError::ZeroWeights)
+        );
+        let host = |name: &str| vec![(name.to_string(), 1)];
+        assert!(generator.set_hosts(host("web1")).is_ok());
+        for invalid in ["a b", "-", "hôst", "a:b", &"h".repeat(256)] {
+            assert!(generator.set_hosts(host(invalid)).is_err(), "{}", invalid);
+        }
+        assert!(generator.set_apps(host("sshd")).is_ok());
+        for invalid in ["a b", "cron[1]", &"a".repeat(49)] {
+            assert!(generator.set_apps(host(invalid)).is_err(), "{}", invalid);
+        }
+        assert_eq!(
+            generator.set_hosts(vec![("a".to_string(), 0)]),
+            Err(GeneratorError::ZeroWeights)
+        );
+        // a rejected setting leaves the previous one
+        let Generated::Valid(msg) = generator.generate() else {
+            unreachable!()
+        };
+        assert_eq!(msg.header.hostname.as_deref(), Some("web1"));
+        assert_eq!(msg.header.appname.as_deref(), Some("sshd"));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::client::{local_hostname, process_name, Sink};
+use crate::clock::{Clock, SystemClock};
+use crate::message::{Facility, Header, Message, Protocol, SdElement, Severity};
+use chrono::SecondsFormat;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// SD-ID of the element holding event and span fields. The enterprise
+/// number is the one reserved for documentation, RFC5612.
+pub const DEFAULT_SD_ID: &str = "tracing@32473";
+
+/// `tracing_subscriber` layer writing each event as an RFC5424 message.
+///
+/// The event's `message` is the MSG. The fields of the spans the event is
+/// in, outermost first, then the event's own fields, become params of one
+/// structured data element. Errors from the sink are ignored.
+///
+/// ```no_run
+/// use syslog_parse::client::{Client, Destination};
+/// use syslog_parse::layer::SyslogLayer;
+/// use tracing_subscriber::prelude::*;
+///
+/// let client = Client::new(Destination::Udp("127.0.0.1:514".parse().unwrap()));
+/// tracing_subscriber::registry().with(SyslogLayer::new(client)).init();
+/// ```
+#[derive(Debug)]
+pub struct SyslogLayer<S> {
+    sink: Mutex<S>,
+    facility: Facility,
+    hostname: Option<String>,
+    appname: Option<String>,
+    procid: String,
+    sd_id: String,
+    clock: Arc<dyn Clock>,
+}
+
+/// Fields recorded on a span, kept in its extensions.
+#[derive(Debug)]
+struct SpanFields(Vec<(String, String)>);
+
+struct FieldVisitor<'a> {
+    params: &'a mut Vec<(String, String)>,
+    message: Option<&'a mut String>,
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.record(field, format!("{:?}", value));
+    }
+}
+
+impl FieldVisitor<'_> {
+    fn record(&mut self, field: &Field, value: String) {
+        match &mut self.message {
+            Some(message) if field.name() == "message" => **message = value,
+            _ => self.params.push((field.name().to_string(), value)),
+        }
+    }
+}
+
+impl<S: Sink> SyslogLayer<S> {
+    pub fn new(sink: S) -> Self {
+        SyslogLayer {
+            sink: Mutex::new(sink),
+            facility: Facility::User,
+            hostname: local_hostname(),
+            appname: process_name(),
+            procid: std::process::id().to_string(),
+            sd_id: DEFAULT_SD_ID.to_string(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// `User` by default.
+    pub fn set_facility(&mut self, facility: Facility) {
+        self.facility = facility;
+    }
+
+    pub fn set_hostname(&mut self, hostname: Option<String>) {
+        self.hostname = hostname;
+    }
+
+    pub fn set_appname(&mut self, appname: Option<String>) {
+        self.appname = appname;
+    }
+
+    pub fn set_sd_id(&mut self, id: &str) {
+        self.sd_id = id.to_string();
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    fn message(&self, severity: Severity, params: Vec<(String, String)>, msg: String) -> Message {
+        let structured_data = if params.is_empty() {
+            Vec::new()
+        } else {
+            vec![SdElement {
+                id: self.sd_id.clone(),
+                params,
+            }]
+        };
+        Message {
+            header: Header {
+                protocol: Protocol::Rfc5424,
+                facility: self.facility,
+                severity,
+                version: Some(1),
+                timestamp: self
+                    .clock
+                    .now()
+                    .to_rfc3339_opts(SecondsFormat::Micros, false),
+                hostname: self.hostname.clone(),
+                appname: self.appname.clone(),
+                procid: Some(self.procid.clone()),
+                msgid: None,
+                structured_data,
+            },
+            msg,
+            truncated: false,
+        }
+    }
+}
+
+impl<S, T> Layer<T> for SyslogLayer<S>
+where
+    S: Sink + 'static,
+    T: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, T>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = SpanFields(Vec::new());
+        attrs.record(&mut FieldVisitor {
+            params: &mut fields.0,
+            message: None,
+        });
+        span.extensions_mut().insert(fields);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, T>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            // a field recorded again replaces its earlier value
+            let mut params = Vec::new();
+            values.record(&mut FieldVisitor {
+                params: &mut params,
+                message: None,
+            });
+            for (name, value) in params {
+                match fields.0.iter_mut().find(|(n, _)| *n == name) {
+                    Some(field) => field.1 = value,
+                    None => fields.0.push((name, value)),
+                }
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, T>) {
+        let mut params = Vec::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(fields) = span.extensions().get::<SpanFields>() {
+                    params.extend(fields.0.iter().cloned());
+                }
+            }
+        }
+        let mut msg = String::new();
+        event.record(&mut FieldVisitor {
+            params: &mut params,
+            message: Some(&mut msg),
+        });
+        let msg = self.message(severity(event.metadata().level()), params, msg);
+        let _ = self.sink.lock().unwrap().send(&msg);
+    }
+}
+
+pub fn severity(level: &Level) -> Severity {
+    match *level {
+        Level::ERROR => Severity::Err,
+        Level::WARN => Severity::Warning,
+        Level::INFO => Severity::Info,
+        _ => Severity::Debug,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_layer() {
+        let (tx, rx) = mpsc::channel();
+        let mut layer = SyslogLayer::new(tx);
+        layer.set_sd_id("app@1");
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(
+                "request",
+                id = 7,
+                path = "/a]b",
+                user = tracing::field::Empty
+            );
+            let _enter = span.enter();
+            span.record("user", "bob");
+            tracing::warn!(quote = "say \"hi\"", back = r"c:\tmp", "slow: {}ms", 12);
+        });
+
+        let msg = rx.try_recv().unwrap();
+        assert_eq!(msg.header.severity, Severity::Warning);
+        assert_eq!(msg.msg, "slow: 12ms");
+        let params = |msg: &Message| msg.header.sd_element("app@1").unwrap().params.clone();
+        let expected: Vec<(String, String)> = [
+            ("id", "7"),
+            ("path", "/a]b"),
+            ("user", "bob"),
+            ("quote", "say \"hi\""),
+            ("back", r"c:\tmp"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(params(&msg), expected);
+
+        // escaped on output, unescaped again by the parser
+        let text = msg.to_string();
+        assert!(text.contains(r#"path="/a\]b""#), "{}", text);
+        let parsed = Message::parse(&text).unwrap();
+        assert_eq!(params(&parsed), expected);
+        assert_eq!(parsed.msg, "slow: 12ms");
+    }
+}
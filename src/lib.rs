@@ -1,7 +1,31 @@
+pub mod client;
+pub mod clock;
+#[cfg(feature = "tokio")]
+pub mod codec;
 pub mod error;
+pub mod field;
+pub mod filter;
 mod format;
+pub mod framing;
+pub mod generator;
+#[cfg(feature = "tracing")]
+pub mod layer;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod message;
+pub mod parser;
 pub mod protocol;
+pub mod reader;
+pub mod replay;
+pub mod router;
+pub mod selector;
+pub mod server;
 pub mod stream;
+pub mod template;
+#[cfg(feature = "tls")]
+pub mod tls;
+
+pub use reader::read_messages;
 
 extern crate serde_derive;
 #[macro_use]
@@ -0,0 +1,201 @@
+use crate::client::{local_hostname, process_name, Sink};
+use crate::clock::{Clock, SystemClock};
+use crate::message::{Facility, Header, Message, Protocol, SdElement, Severity};
+use chrono::SecondsFormat;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::sync::{Arc, Mutex};
+
+/// SD-ID of the element holding the record's location. The enterprise
+/// number is the one reserved for documentation, RFC5612.
+pub const DEFAULT_SD_ID: &str = "rust@32473";
+
+/// `log::Log` implementation writing RFC5424 messages to a `Sink`.
+///
+/// HOSTNAME, APP-NAME and PROCID are filled from the running process. The
+/// record's target, module path, file and line go in a structured data
+/// element, `[rust@32473 target="..." module="..." file="..." line="..."]`.
+/// Errors from the sink are ignored, as logging must not fail.
+///
+/// ```no_run
+/// use syslog_parse::client::{Client, Destination};
+/// use syslog_parse::logger::SyslogLogger;
+///
+/// let client = Client::new(Destination::Udp("127.0.0.1:514".parse().unwrap()));
+/// SyslogLogger::new(client).init().unwrap();
+/// log::info!("started");
+/// ```
+#[derive(Debug)]
+pub struct SyslogLogger<S> {
+    sink: Mutex<S>,
+    level: LevelFilter,
+    facility: Facility,
+    hostname: Option<String>,
+    appname: Option<String>,
+    procid: String,
+    sd_id: String,
+    clock: Arc<dyn Clock>,
+}
+
+impl<S: Sink> SyslogLogger<S> {
+    pub fn new(sink: S) -> Self {
+        SyslogLogger {
+            sink: Mutex::new(sink),
+            level: LevelFilter::Info,
+            facility: Facility::User,
+            hostname: local_hostname(),
+            appname: process_name(),
+            procid: std::process::id().to_string(),
+            sd_id: DEFAULT_SD_ID.to_string(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Most verbose level logged, `Info` by default.
+    pub fn set_level(&mut self, level: LevelFilter) {
+        self.level = level;
+    }
+
+    /// `User` by default.
+    pub fn set_facility(&mut self, facility: Facility) {
+        self.facility = facility;
+    }
+
+    pub fn set_hostname(&mut self, hostname: Option<String>) {
+        self.hostname = hostname;
+    }
+
+    pub fn set_appname(&mut self, appname: Option<String>) {
+        self.appname = appname;
+    }
+
+    pub fn set_sd_id(&mut self, id: &str) {
+        self.sd_id = id.to_string();
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Install as the global logger and set the maximum level.
+    pub fn init(self) -> Result<(), SetLoggerError>
+    where
+        S: 'static,
+    {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+
+    /// The message written for `record`.
+    pub fn message(&self, record: &Record<'_>) -> Message {
+        let mut params = vec![("target".to_string(), record.target().to_string())];
+        let location = [
+            ("module", record.module_path().map(str::to_string)),
+            ("file", record.file().map(str::to_string)),
+            ("line", record.line().map(|line| line.to_string())),
+        ];
+        for (name, value) in location {
+            if let Some(value) = value {
+                params.push((name.to_string(), value));
+            }
+        }
+        Message {
+            header: Header {
+                protocol: Protocol::Rfc5424,
+                facility: self.facility,
+                severity: severity(record.level()),
+                version: Some(1),
+                timestamp: self
+                    .clock
+                    .now()
+                    .to_rfc3339_opts(SecondsFormat::Micros, false),
+                hostname: self.hostname.clone(),
+                appname: self.appname.clone(),
+                procid: Some(self.procid.clone()),
+                msgid: None,
+                structured_data: vec![SdElement {
+                    id: self.sd_id.clone(),
+                    params,
+                }],
+            },
+            msg: record.args().to_string(),
+            truncated: false,
+        }
+    }
+}
+
+impl<S: Sink> Log for SyslogLogger<S> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            let msg = self.message(record);
+            let _ = self.sink.lock().unwrap().send(&msg);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.sink.lock().unwrap().flush();
+    }
+}
+
+pub fn severity(level: Level) -> Severity {
+    match level {
+        Level::Error => Severity::Err,
+        Level::Warn => Severity::Warning,
+        Level::Info => Severity::Info,
+        Level::Debug | Level::Trace => Severity::Debug,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn test_message() {
+        let mut logger = SyslogLogger::new(Vec::new());
+        logger.set_level(LevelFilter::Debug);
+        logger.set_facility(Facility::Local3);
+        logger.set_hostname(Some("web 1".to_string()));
+        logger.set_appname(Some("api".to_string()));
+        let now = Local.with_ymd_and_hms(2023, 9, 13, 11, 15, 47).unwrap();
+        logger.set_clock(Arc::new(ManualClock::new(now)));
+
+        for level in [Level::Warn, Level::Trace] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("api::db")
+                    .module_path(Some("api::db"))
+                    .file(Some("src/db.rs"))
+                    .line(Some(42))
+                    .args(format_args!("query took \"{}\" ms]", 12))
+                    .build(),
+            );
+        }
+        logger.flush();
+
+        let sent = logger.sink.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        let msg = &sent[0];
+        assert_eq!(msg.header.pri(), 19 << 3 | 4);
+        assert_eq!(msg.header.procid, Some(std::process::id().to_string()));
+        assert_eq!(
+            msg.header.timestamp,
+            now.to_rfc3339_opts(SecondsFormat::Micros, false)
+        );
+        let sd = msg.header.sd_element(DEFAULT_SD_ID).unwrap();
+        assert_eq!(sd.param("file"), Some("src/db.rs"));
+        assert_eq!(sd.param("line"), Some("42"));
+
+        // the host name is cleaned up on output, the rest comes back as it was
+        let parsed = Message::parse(&msg.to_string()).unwrap();
+        assert_eq!(parsed.header.hostname.as_deref(), Some("web_1"));
+        assert_eq!(parsed.header.structured_data, msg.header.structured_data);
+        assert_eq!(parsed.msg, "query took \"12\" ms]");
+    }
+}
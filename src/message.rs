@@ -0,0 +1,357 @@
+use crate::error::ErrorKind;
+pub use crate::format::pri::{Facility, Severity};
+use crate::protocol::parse_fields;
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDateTime, TimeZone};
+use serde_derive::Serialize;
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Rfc5424,
+    Rfc3164,
+}
+
+/// One structured data element, `[id name="value" ...]`.
+/// Param values are stored unescaped.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct SdElement {
+    pub id: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl SdElement {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// The parsed header fields of a message. RFC5424 NILVALUEs (`-`) are `None`.
+/// For RFC3164 the TAG is split into `appname` and `procid` (`sshd[123]`).
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Header {
+    pub protocol: Protocol,
+    pub facility: Facility,
+    pub severity: Severity,
+    pub version: Option<u32>,
+    pub timestamp: String,
+    pub hostname: Option<String>,
+    pub appname: Option<String>,
+    pub procid: Option<String>,
+    pub msgid: Option<String>,
+    pub structured_data: Vec<SdElement>,
+}
+
+impl Header {
+    pub fn pri(&self) -> u8 {
+        self.facility.code() << 3 | self.severity.code()
+    }
+
+    pub fn sd_element(&self, id: &str) -> Option<&SdElement> {
+        self.structured_data.iter().find(|e| e.id == id)
+    }
+}
+
+/// A fully parsed syslog message.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Message {
+    #[serde(flatten)]
+    pub header: Header,
+    pub msg: String,
+    /// Set when `MsgStream` cut the message at its size limit.
+    #[serde(skip_serializing_if = "is_false")]
+    pub truncated: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl Message {
+    /// Parse a single message, e.g. one UDP datagram.
+    pub fn parse(input: &str) -> Result<Message, ErrorKind> {
+        match parse_fields(input) {
+            Ok((_, (msg, header))) => Ok(Message {
+                header,
+                msg: msg.msg.to_string(),
+                truncated: false,
+            }),
+            Err(_) => Err(ErrorKind::Header),
+        }
+    }
+
+    /// The TIMESTAMP as a date. RFC3164 ones without a year get the one
+    /// that puts them closest to now.
+    pub fn timestamp(&self) -> Option<DateTime<FixedOffset>> {
+        self.timestamp_near(Local::now().fixed_offset())
+    }
+
+    /// `timestamp`, picking the year closest to `near`, e.g. the previous
+    /// message's timestamp, so a capture can cross New Year.
+    pub fn timestamp_near(&self, near: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        let text = self.header.timestamp.trim_end_matches(':');
+        if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+            return Some(timestamp);
+        }
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        let at = |year: Option<i32>| {
+            let mut parts = parts.clone();
+            let year = year.map(|y| y.to_string());
+            if let Some(year) = &year {
+                parts.insert(2, year);
+            }
+            let naive =
+                NaiveDateTime::parse_from_str(&parts.join(" "), "%b %d %Y %H:%M:%S").ok()?;
+            Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|t| t.fixed_offset())
+        };
+        if parts.len() != 3 {
+            return at(None);
+        }
+        // Feb 29 may be up to 4 years away.
+        (near.year() - 4..=near.year() + 4)
+            .filter_map(|year| at(Some(year)))
+            .min_by_key(|t| (*t - near).num_seconds().abs())
+    }
+}
+
+/// `[id name="value" ...]`, escaped as RFC5424 requires.
+impl Display for SdElement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}", sd_name(&self.id))?;
+        for (name, value) in &self.params {
+            write!(f, " {}=\"{}\"", sd_name(name), escape_param(value))?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Protocol::Rfc5424 => "rfc5424",
+            Protocol::Rfc3164 => "rfc3164",
+        })
+    }
+}
+
+/// Writes the message in its protocol's format, so that it parses back to
+/// an equal message. Header fields that would break parsing (whitespace,
+/// `:` in an RFC3164 TAG, `=`, `]` or `"` in SD names) are replaced by `_`.
+/// RFC3164 structured data goes after the TAG, so it is lost on a message
+/// without one.
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let h = &self.header;
+        match h.protocol {
+            Protocol::Rfc5424 => {
+                write!(
+                    f,
+                    "<{}>{} {} {} {} {} {} ",
+                    h.pri(),
+                    h.version.unwrap_or(1),
+                    h.timestamp,
+                    nil(&h.hostname),
+                    nil(&h.appname),
+                    nil(&h.procid),
+                    nil(&h.msgid)
+                )?;
+                if h.structured_data.is_empty() {
+                    f.write_str("-")?;
+                }
+                for element in &h.structured_data {
+                    element.fmt(f)?;
+                }
+                if !self.msg.is_empty() {
+                    write!(f, " {}", self.msg)?;
+                }
+                Ok(())
+            }
+            Protocol::Rfc3164 => {
+                write!(f, "<{}>{}", h.pri(), h.timestamp)?;
+                if let Some(hostname) = &h.hostname {
+                    write!(f, " {}", token(hostname, ":"))?;
+                }
+                if let Some(appname) = &h.appname {
+                    write!(f, " {}", token(appname, ":["))?;
+                    if let Some(procid) = &h.procid {
+                        write!(f, "[{}]", token(procid, ":]"))?;
+                    }
+                    f.write_str(":")?;
+                    if !h.structured_data.is_empty() {
+                        f.write_str(" ")?;
+                        for element in &h.structured_data {
+                            element.fmt(f)?;
+                        }
+                    }
+                }
+                write!(f, " {}", self.msg)
+            }
+        }
+    }
+}
+
+/// A header field, or the NILVALUE `-`.
+fn nil(value: &Option<String>) -> Cow<'_, str> {
+    match value {
+        Some(value) if !value.is_empty() => token(value, ""),
+        _ => Cow::Borrowed("-"),
+    }
+}
+
+fn sd_name(name: &str) -> Cow<'_, str> {
+    token(name, "=]\"")
+}
+
+/// Replace whitespace and the `reserved` characters by `_`.
+fn token<'a>(value: &'a str, reserved: &str) -> Cow<'a, str> {
+    let bad = |c: char| c.is_whitespace() || reserved.contains(c);
+    if value.is_empty() {
+        Cow::Borrowed("-")
+    } else if value.contains(bad) {
+        Cow::Owned(value.replace(bad, "_"))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Escape `"`, `\` and `]` in a param value, RFC5424 section 6.3.3.
+fn escape_param(value: &str) -> Cow<'_, str> {
+    if !value.contains(['"', '\\', ']']) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp() {
+        let msg = Message::parse("<13>1 2024-03-01T12:00:00.5+02:00 h app - - - x").unwrap();
+        assert_eq!(
+            msg.timestamp().unwrap().to_rfc3339(),
+            "2024-03-01T12:00:00.500+02:00"
+        );
+        let a = Message::parse("<13>Mar  1 12:30:05 web1 sshd: x").unwrap();
+        let b = Message::parse("<13>Mar  1 12:31:00 web1 sshd: x").unwrap();
+        assert_eq!(
+            b.timestamp().unwrap() - a.timestamp().unwrap(),
+            chrono::Duration::seconds(55)
+        );
+        let c = Message::parse("<13>Mar  1 2024 12:30:05 web1 sshd: x").unwrap();
+        assert_eq!(c.timestamp().unwrap().year(), 2024);
+    }
+
+    #[test]
+    fn test_timestamp_near() {
+        let at = |text: &str| {
+            Local
+                .from_local_datetime(
+                    &NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap(),
+                )
+                .unwrap()
+                .fixed_offset()
+        };
+        let near = at("2024-12-31 23:59:59");
+        let msg = Message::parse("<13>Jan  1 00:00:01 web1 sshd: x").unwrap();
+        assert_eq!(msg.timestamp_near(near).unwrap(), at("2025-01-01 00:00:01"));
+        let msg = Message::parse("<13>Dec 31 23:59:58 web1 sshd: x").unwrap();
+        assert_eq!(msg.timestamp_near(near).unwrap(), at("2024-12-31 23:59:58"));
+        let msg = Message::parse("<13>Feb 29 12:00:00 web1 sshd: x").unwrap();
+        assert_eq!(
+            msg.timestamp_near(at("2025-03-01 00:00:00")).unwrap(),
+            at("2024-02-29 12:00:00")
+        );
+        // a year in the header wins
+        let msg = Message::parse("<13>Mar  1 2020 12:30:05 web1 sshd: x").unwrap();
+        assert_eq!(msg.timestamp_near(near).unwrap(), at("2020-03-01 12:30:05"));
+    }
+
+    #[test]
+    fn test_parse_5424() {
+        let msg = Message::parse(r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="App\"lication" eventID="1011"] An application event log entry"#).unwrap();
+        assert_eq!(msg.header.protocol, Protocol::Rfc5424);
+        assert_eq!(msg.header.facility, Facility::Local4);
+        assert_eq!(msg.header.severity, Severity::Notice);
+        assert_eq!(msg.header.pri(), 165);
+        assert_eq!(msg.header.version, Some(1));
+        assert_eq!(msg.header.timestamp, "2003-10-11T22:14:15.003Z");
+        assert_eq!(
+            msg.header.hostname.as_deref(),
+            Some("mymachine.example.com")
+        );
+        assert_eq!(msg.header.appname.as_deref(), Some("evntslog"));
+        assert_eq!(msg.header.procid, None);
+        assert_eq!(msg.header.msgid.as_deref(), Some("ID47"));
+        let sd = msg.header.sd_element("exampleSDID@32473").unwrap();
+        assert_eq!(sd.param("iut"), Some("3"));
+        assert_eq!(sd.param("eventSource"), Some("App\"lication"));
+        assert_eq!(msg.msg, "An application event log entry");
+    }
+
+    #[test]
+    fn test_parse_3164() {
+        let msg =
+            Message::parse("<34>Oct 11 22:14:15 mymachine su[123]: 'su root' failed").unwrap();
+        assert_eq!(msg.header.protocol, Protocol::Rfc3164);
+        assert_eq!(msg.header.facility, Facility::Auth);
+        assert_eq!(msg.header.severity, Severity::Crit);
+        assert_eq!(msg.header.version, None);
+        assert_eq!(msg.header.timestamp, "Oct 11 22:14:15");
+        assert_eq!(msg.header.hostname.as_deref(), Some("mymachine"));
+        assert_eq!(msg.header.appname.as_deref(), Some("su"));
+        assert_eq!(msg.header.procid.as_deref(), Some("123"));
+        assert_eq!(msg.msg, "'su root' failed");
+
+        let msg = Message::parse("<13>Oct 11 22:14:15 myapp: no hostname").unwrap();
+        assert_eq!(msg.header.hostname, None);
+        assert_eq!(msg.header.appname.as_deref(), Some("myapp"));
+        assert_eq!(msg.msg, "no hostname");
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let inputs = [
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="App\"li\]cation\\"][x@1 a=""] An application event log entry"#,
+            "<34>1 2003-10-11T22:14:15.003Z - - - - -",
+            "<34>Oct 11 22:14:15 mymachine su[123]: 'su root' failed",
+            "<13>Oct 11 22:14:15 myapp: no hostname",
+            r#"<34>Oct 11 22:14:15 host su: [a@1 x="1"][b@2] with sd"#,
+        ];
+        for input in inputs {
+            let msg = Message::parse(input).unwrap();
+            assert_eq!(msg.to_string(), input);
+            assert_eq!(Message::parse(&msg.to_string()).unwrap(), msg);
+        }
+
+        let mut msg = Message::parse(inputs[1]).unwrap();
+        msg.header.hostname = Some("my host".to_string());
+        msg.header.appname = Some(String::new());
+        msg.msg = "body".to_string();
+        assert_eq!(
+            msg.to_string(),
+            "<34>1 2003-10-11T22:14:15.003Z my_host - - - - body"
+        );
+
+        // no TAG to put the structured data after
+        let mut msg = Message::parse(inputs[4]).unwrap();
+        assert_eq!(msg.header.structured_data.len(), 2);
+        msg.header.appname = None;
+        assert_eq!(msg.to_string(), "<34>Oct 11 22:14:15 host with sd");
+    }
+}
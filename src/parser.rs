@@ -0,0 +1,584 @@
+use crate::error::ErrorKind;
+use crate::framing::DEFAULT_MAX_FRAME_LEN;
+use crate::message::{Header, Message};
+use crate::protocol::parse_fields;
+use crate::stream::MessageResult;
+use memchr::memchr;
+use std::borrow::Cow;
+
+/// Longest header the parser waits for before deciding on a candidate.
+const MAX_HEADER_LEN: usize = 8 * 1024;
+
+/// Incremental replacement for `MsgStream`.
+///
+/// Input is appended once to an internal buffer and scanned once. A `<`
+/// starts a header candidate whose progress (PRI digits, fields seen,
+/// structured data nesting) is kept between `feed` calls, so a header that
+/// arrives over many small reads is not parsed again from scratch. The full
+/// header parser runs once per candidate, when enough input is there to
+/// decide. Frames borrow from the buffer.
+///
+/// A message longer than the frame limit is cut at the limit and reported
+/// as `ErrorKind::TooLarge`; the rest of it is dropped as it arrives, so
+/// the buffer stays bounded without a header in sight.
+///
+/// ```ignore
+/// parser.feed(&chunk);
+/// while let Some(frame) = parser.next_frame() {
+///     handle(frame.into_message());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct StreamParser {
+    buf: Vec<u8>,
+    /// Stream offset of `buf[0]`.
+    base: u64,
+    /// Start of the message being collected.
+    start: usize,
+    /// Everything before this offset has been scanned for `<`.
+    scan: usize,
+    header: Option<(Header, usize)>,
+    candidate: Option<Candidate>,
+    max_frame_len: usize,
+    /// The current message went over `max_frame_len` and was reported;
+    /// drop its bytes until the next header.
+    skip: bool,
+    eof: bool,
+}
+
+/// A message cut out of the stream.
+#[derive(Debug)]
+pub struct Frame<'a> {
+    /// The message text, borrowed unless it had to be decoded lossily.
+    pub raw: Cow<'a, str>,
+    /// Where the message starts in the stream, in bytes.
+    pub offset: u64,
+    bytes: &'a [u8],
+    header: Option<(Header, usize)>,
+    too_large: bool,
+}
+
+impl Frame<'_> {
+    /// The message as received, before any lossy decoding.
+    pub fn bytes(&self) -> &[u8] {
+        self.bytes
+    }
+
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref().map(|(header, _)| header)
+    }
+
+    pub fn into_message(self) -> MessageResult {
+        if self.too_large {
+            return Err((self.raw.into_owned(), ErrorKind::TooLarge));
+        }
+        match self.header {
+            Some((header, len)) => Ok(Message {
+                header,
+                msg: self.raw.get(len..).unwrap_or_default().to_string(),
+                truncated: false,
+            }),
+            None => Err((self.raw.into_owned(), ErrorKind::Header)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    /// Offset of the `<`.
+    pos: usize,
+    /// Next byte to look at.
+    at: usize,
+    state: State,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum State {
+    Pri {
+        digits: u8,
+        value: u16,
+    },
+    /// First byte after `>`: a version digit (RFC5424) or a timestamp (RFC3164).
+    AfterPri,
+    /// Digits after `>`, either the RFC5424 VERSION or an RFC3339 timestamp.
+    Version,
+    /// RFC5424: space separated fields seen so far, out of
+    /// VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID.
+    Fields5424 {
+        fields: u8,
+        in_token: bool,
+    },
+    /// RFC3164: timestamp tokens, then up to two of HOSTNAME and TAG.
+    Timestamp3164 {
+        tokens: u8,
+        in_token: bool,
+        colon: bool,
+    },
+    Fields3164 {
+        tokens: u8,
+        in_token: bool,
+    },
+    /// Spaces and `:` between the RFC3164 TAG and the message.
+    AfterTag,
+    Sd(Sd),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Sd {
+    Start,
+    Element,
+    Quoted,
+    Escaped,
+    Closed,
+    /// Spaces after the structured data, part of the header.
+    Trailing,
+}
+
+enum Step {
+    NeedMore,
+    Reject,
+    Decide,
+}
+
+impl Default for StreamParser {
+    fn default() -> Self {
+        StreamParser {
+            buf: Vec::new(),
+            base: 0,
+            start: 0,
+            scan: 0,
+            header: None,
+            candidate: None,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            skip: false,
+            eof: false,
+        }
+    }
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        StreamParser::default()
+    }
+
+    /// Longest message, header included, 64 KiB by default.
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.max_frame_len = len;
+    }
+
+    /// Append a chunk of input.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.compact();
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Mark the end of input: a pending header candidate is decided with
+    /// what is there and the last message is emitted by `next_frame`.
+    pub fn finish(&mut self) {
+        self.eof = true;
+    }
+
+    /// Bytes held for the message being collected.
+    pub fn pending(&self) -> usize {
+        self.buf.len() - self.start
+    }
+
+    pub fn next_frame(&mut self) -> Option<Frame<'_>> {
+        loop {
+            let Some(mut candidate) = self.candidate.take() else {
+                match memchr(b'<', &self.buf[self.scan..]) {
+                    Some(i) => {
+                        let pos = self.scan + i;
+                        self.candidate = Some(Candidate {
+                            pos,
+                            at: pos + 1,
+                            state: State::Pri {
+                                digits: 0,
+                                value: 0,
+                            },
+                        });
+                        continue;
+                    }
+                    None => {
+                        self.scan = self.buf.len();
+                        if self.overflows(self.buf.len()) {
+                            return self.too_large();
+                        }
+                        return self.take_last();
+                    }
+                }
+            };
+
+            let step = candidate.advance(&self.buf);
+            let step = match step {
+                Step::NeedMore if self.eof => Step::Decide,
+                Step::NeedMore if candidate.at - candidate.pos >= MAX_HEADER_LEN => Step::Decide,
+                step => step,
+            };
+            match step {
+                Step::NeedMore => {
+                    self.candidate = Some(candidate);
+                    if self.overflows(candidate.pos) {
+                        return self.too_large();
+                    }
+                    return None;
+                }
+                Step::Reject => self.scan = candidate.pos + 1,
+                Step::Decide => match parse_header(&self.buf[candidate.pos..candidate.at]) {
+                    Some((header, len)) => {
+                        let start = self.start;
+                        let previous = self.header.replace((header, len));
+                        self.start = candidate.pos;
+                        self.scan = candidate.pos + len.max(1);
+                        if std::mem::take(&mut self.skip) {
+                            continue;
+                        }
+                        if candidate.pos > start {
+                            let too_large = candidate.pos - start > self.max_frame_len;
+                            let end = match too_large {
+                                true => start + self.max_frame_len,
+                                false => candidate.pos,
+                            };
+                            return Some(Frame {
+                                raw: String::from_utf8_lossy(&self.buf[start..end]),
+                                bytes: &self.buf[start..end],
+                                offset: self.base + start as u64,
+                                header: previous,
+                                too_large,
+                            });
+                        }
+                    }
+                    None => self.scan = candidate.pos + 1,
+                },
+            }
+        }
+    }
+
+    /// Whether the current message, known to run up to `end`, is over the
+    /// limit and not reported yet. Bytes of one already reported are
+    /// dropped up to `end`.
+    fn overflows(&mut self, end: usize) -> bool {
+        if self.skip {
+            self.start = end;
+            self.scan = self.scan.max(end);
+            return false;
+        }
+        end - self.start > self.max_frame_len
+    }
+
+    /// Report the current message, cut at the limit, and drop the rest.
+    fn too_large(&mut self) -> Option<Frame<'_>> {
+        let start = self.start;
+        let end = self.candidate.map_or(self.scan, |candidate| candidate.pos);
+        self.start = end;
+        self.scan = self.scan.max(end);
+        self.skip = true;
+        Some(Frame {
+            raw: String::from_utf8_lossy(&self.buf[start..start + self.max_frame_len]),
+            bytes: &self.buf[start..start + self.max_frame_len],
+            offset: self.base + start as u64,
+            header: self.header.take(),
+            too_large: true,
+        })
+    }
+
+    /// At end of input, emit what is left and reset.
+    fn take_last(&mut self) -> Option<Frame<'_>> {
+        if !self.eof {
+            return None;
+        }
+        self.eof = false;
+        self.skip = false;
+        let start = self.start;
+        let header = self.header.take();
+        self.start = self.buf.len();
+        self.scan = self.buf.len();
+        if start == self.buf.len() {
+            return None;
+        }
+        Some(Frame {
+            raw: String::from_utf8_lossy(&self.buf[start..]),
+            bytes: &self.buf[start..],
+            offset: self.base + start as u64,
+            header,
+            too_large: false,
+        })
+    }
+
+    /// Drop the bytes of messages already returned.
+    fn compact(&mut self) {
+        // only once at least half of the buffer is done with, so a long
+        // message arriving in small reads is not moved over and over
+        if self.start == 0 || self.start < self.buf.len() / 2 {
+            return;
+        }
+        self.buf.drain(..self.start);
+        self.base += self.start as u64;
+        self.scan -= self.start;
+        if let Some(candidate) = self.candidate.as_mut() {
+            candidate.pos -= self.start;
+            candidate.at -= self.start;
+        }
+        self.start = 0;
+    }
+}
+
+/// Run the full header parser once the candidate is decided.
+fn parse_header(input: &[u8]) -> Option<(Header, usize)> {
+    let input = match std::str::from_utf8(input) {
+        Ok(input) => input,
+        // the header ends before a character that is cut off or invalid
+        Err(e) => std::str::from_utf8(&input[..e.valid_up_to()]).ok()?,
+    };
+    let (_, (msg, header)) = parse_fields(input).ok()?;
+    Some((header, msg.header.len()))
+}
+
+impl Candidate {
+    /// Move over the bytes that arrived since the last call.
+    fn advance(&mut self, buf: &[u8]) -> Step {
+        while self.at < buf.len() {
+            let b = buf[self.at];
+            self.at += 1;
+            self.state = match self.state {
+                State::Pri { digits, value } => match b {
+                    b'0'..=b'9' if digits < 3 => State::Pri {
+                        digits: digits + 1,
+                        value: value * 10 + (b - b'0') as u16,
+                    },
+                    b'>' if digits > 0 && value <= 191 => State::AfterPri,
+                    _ => return Step::Reject,
+                },
+                State::AfterPri => match b {
+                    b'0'..=b'9' => State::Version,
+                    b' ' => State::AfterPri,
+                    b if b.is_ascii_alphabetic() => State::Timestamp3164 {
+                        tokens: 0,
+                        in_token: true,
+                        colon: false,
+                    },
+                    _ => return Step::Reject,
+                },
+                State::Version => match b {
+                    b'0'..=b'9' => State::Version,
+                    b' ' => State::Fields5424 {
+                        fields: 1,
+                        in_token: false,
+                    },
+                    // an RFC3339 timestamp, as accepted by RFC3164 parsing
+                    b'-' => State::Timestamp3164 {
+                        tokens: 0,
+                        in_token: true,
+                        colon: true,
+                    },
+                    _ => return Step::Reject,
+                },
+                State::Fields5424 { fields, in_token } => match (b == b' ', in_token) {
+                    (true, true) if fields == 5 => State::Sd(Sd::Start),
+                    (true, true) => State::Fields5424 {
+                        fields: fields + 1,
+                        in_token: false,
+                    },
+                    (true, false) => State::Fields5424 { fields, in_token },
+                    (false, _) if b.is_ascii_whitespace() => return Step::Reject,
+                    (false, _) => State::Fields5424 {
+                        fields,
+                        in_token: true,
+                    },
+                },
+                State::Timestamp3164 {
+                    tokens,
+                    in_token,
+                    colon,
+                } => {
+                    if b != b' ' {
+                        State::Timestamp3164 {
+                            tokens,
+                            in_token: true,
+                            colon: colon || (tokens == 2 && b == b':'),
+                        }
+                    } else if !in_token {
+                        self.state
+                    } else if colon || tokens == 3 {
+                        // MMM DD HH:MM:SS, MMM DD YYYY HH:MM:SS or RFC3339
+                        State::Fields3164 {
+                            tokens: 0,
+                            in_token: false,
+                        }
+                    } else {
+                        State::Timestamp3164 {
+                            tokens: tokens + 1,
+                            in_token: false,
+                            colon,
+                        }
+                    }
+                }
+                State::Fields3164 { tokens, in_token } => match b {
+                    b':' => State::AfterTag,
+                    b' ' if in_token && tokens == 1 => State::AfterTag,
+                    b' ' if in_token => State::Fields3164 {
+                        tokens: tokens + 1,
+                        in_token: false,
+                    },
+                    b'[' if !in_token && tokens > 0 => State::Sd(Sd::Element),
+                    b if b.is_ascii_whitespace() => return Step::Decide,
+                    _ => State::Fields3164 {
+                        tokens,
+                        in_token: true,
+                    },
+                },
+                State::AfterTag => match b {
+                    b' ' | b':' => State::AfterTag,
+                    b'[' => State::Sd(Sd::Element),
+                    _ => return Step::Decide,
+                },
+                State::Sd(sd) => match (sd, b) {
+                    (Sd::Start, b' ') => State::Sd(Sd::Start),
+                    (Sd::Start, b'-') | (Sd::Closed, b' ') | (Sd::Trailing, b' ') => {
+                        State::Sd(Sd::Trailing)
+                    }
+                    (Sd::Start, b'[') | (Sd::Closed, b'[') => State::Sd(Sd::Element),
+                    (Sd::Start, _) | (Sd::Closed, _) | (Sd::Trailing, _) => return Step::Decide,
+                    (Sd::Element, b'"') => State::Sd(Sd::Quoted),
+                    (Sd::Element, b']') => State::Sd(Sd::Closed),
+                    (Sd::Element, _) => State::Sd(Sd::Element),
+                    (Sd::Quoted, b'\\') => State::Sd(Sd::Escaped),
+                    (Sd::Quoted, b'"') => State::Sd(Sd::Element),
+                    (Sd::Quoted, _) | (Sd::Escaped, _) => State::Sd(Sd::Quoted),
+                },
+            };
+        }
+        Step::NeedMore
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Protocol;
+
+    fn collect(parser: &mut StreamParser) -> Vec<String> {
+        let mut frames = Vec::new();
+        while let Some(frame) = parser.next_frame() {
+            frames.push(frame.raw.into_owned());
+        }
+        frames
+    }
+
+    #[test]
+    fn test_max_frame_len() {
+        let header = "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - ";
+        let mut parser = StreamParser::new();
+        parser.set_max_frame_len(100);
+        parser.feed(header.as_bytes());
+        // a body that never ends, with a '<' now and then
+        for _ in 0..1000 {
+            parser.feed(b"0123456789 <x ");
+            let frames: Vec<_> =
+                std::iter::from_fn(|| parser.next_frame().map(Frame::into_message)).collect();
+            for frame in frames {
+                let (raw, kind) = frame.unwrap_err();
+                assert_eq!(kind, ErrorKind::TooLarge);
+                assert_eq!(raw.len(), 100);
+                assert!(raw.starts_with(header));
+            }
+            assert!(parser.pending() <= 100);
+        }
+        // the next message is whole again
+        parser.feed(MSGS.as_bytes());
+        let frames = collect(&mut parser);
+        assert_eq!(frames.len(), 3);
+        assert!(frames[0].starts_with("<46>1 2023-09-13T11:15:47.697131Z"));
+
+        // a complete message over the limit
+        let mut parser = StreamParser::new();
+        parser.set_max_frame_len(80);
+        parser.feed(MSGS.as_bytes());
+        parser.finish();
+        let frames: Vec<_> =
+            std::iter::from_fn(|| parser.next_frame().map(Frame::into_message)).collect();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].as_ref().unwrap().msg, "hello world");
+        assert_eq!(frames[1].as_ref().unwrap_err().1, ErrorKind::TooLarge);
+        assert!(frames[3].is_ok());
+    }
+
+    const MSGS: &str = "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - hello world<46>1 2023-09-13T11:15:47.707412Z localhost dygen 48747 186 [id@1 a=\"x]\"] hello 传输<14>Oct 11 22:14:15 host su[42]: a <b> c<13>2023-09-13T11:15:47Z app: last";
+
+    #[test]
+    fn test_whole_input() {
+        let mut parser = StreamParser::new();
+        parser.feed(MSGS.as_bytes());
+        let mut frames = collect(&mut parser);
+        parser.finish();
+        frames.extend(collect(&mut parser));
+        assert_eq!(
+            frames,
+            vec![
+                "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - hello world",
+                "<46>1 2023-09-13T11:15:47.707412Z localhost dygen 48747 186 [id@1 a=\"x]\"] hello 传输",
+                "<14>Oct 11 22:14:15 host su[42]: a <b> c",
+                "<13>2023-09-13T11:15:47Z app: last",
+            ]
+        );
+        assert_eq!(parser.pending(), 0);
+    }
+
+    #[test]
+    fn test_byte_by_byte() {
+        let mut parser = StreamParser::new();
+        let mut messages = Vec::new();
+        let mut offsets = Vec::new();
+        for b in MSGS.as_bytes() {
+            parser.feed(&[*b]);
+            while let Some(frame) = parser.next_frame() {
+                offsets.push(frame.offset as usize);
+                messages.push(frame.into_message());
+            }
+        }
+        parser.finish();
+        while let Some(frame) = parser.next_frame() {
+            offsets.push(frame.offset as usize);
+            messages.push(frame.into_message());
+        }
+        assert_eq!(messages.len(), 4);
+        let starts = [
+            "<46>1 2023-09-13T11:15:47.697",
+            "<46>1 2023-09-13T11:15:47.707",
+            "<14>",
+            "<13>",
+        ];
+        assert_eq!(offsets, starts.map(|start| MSGS.find(start).unwrap()));
+        let second = messages[1].as_ref().unwrap();
+        assert_eq!(
+            second.header.sd_element("id@1").unwrap().param("a"),
+            Some("x]")
+        );
+        assert_eq!(second.msg, "hello 传输");
+        let third = messages[2].as_ref().unwrap();
+        assert_eq!(third.header.protocol, Protocol::Rfc3164);
+        assert_eq!(third.header.hostname.as_deref(), Some("host"));
+        assert_eq!(third.header.appname.as_deref(), Some("su"));
+        assert_eq!(third.msg, "a <b> c");
+        let last = messages[3].as_ref().unwrap();
+        assert_eq!(last.header.appname.as_deref(), Some("app"));
+        assert_eq!(last.msg, "last");
+    }
+
+    #[test]
+    fn test_garbage_and_incomplete_header() {
+        let mut parser = StreamParser::new();
+        parser.feed(b"garbage<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - hi<46>1 2023-09-13T11:15");
+        let frame = parser.next_frame().unwrap().into_message();
+        assert_eq!(frame, Err(("garbage".to_string(), ErrorKind::Header)));
+        assert!(parser.next_frame().is_none());
+
+        parser.finish();
+        let frame = parser.next_frame().unwrap();
+        assert_eq!(
+            frame.raw,
+            "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - hi<46>1 2023-09-13T11:15"
+        );
+        assert!(parser.next_frame().is_none());
+    }
+}
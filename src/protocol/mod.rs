@@ -1,3 +1,4 @@
+use crate::message::Header;
 use nom::branch::alt;
 use nom::IResult;
 
@@ -10,10 +11,20 @@ pub struct Msg<'a> {
     pub msg: &'a str,
 }
 
-pub fn single_parse(input: &str) -> IResult<&str, Msg> {
+/// Map the RFC5424 NILVALUE `-` to `None`.
+fn nil(value: Option<&str>) -> Option<String> {
+    value.filter(|v| *v != "-").map(str::to_string)
+}
+
+pub fn single_parse(input: &str) -> IResult<&str, Msg<'_>> {
     alt((rfc5424::parse, rfc3164::parse))(input)
 }
 
+/// Like `single_parse`, also returning the header parsed into its fields.
+pub(crate) fn parse_fields(input: &str) -> IResult<&str, (Msg<'_>, Header)> {
+    alt((rfc5424::parse_fields, rfc3164::parse_fields))(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,23 +1,29 @@
 use crate::format::hostname::hostname;
-use crate::format::pri::pri;
+use crate::format::pri::pri_parts;
 use crate::format::structured_data::structured_data_optional;
 use crate::format::tagname::tagname;
 use crate::format::timestamp::timestamp_3164;
+use crate::message::{Header, Protocol};
 use crate::protocol::Msg;
 use nom::bytes::complete::tag;
 use nom::character::complete::space0;
 use nom::combinator::{map, opt, rest};
-use nom::sequence::{preceded, tuple};
+use nom::sequence::{pair, tuple};
 use nom::IResult;
 
-pub(crate) fn parse(input: &str) -> IResult<&str, Msg> {
+pub(crate) fn parse(input: &str) -> IResult<&str, Msg<'_>> {
+    map(parse_fields, |(msg, _)| msg)(input)
+}
+
+/// `parse`, also returning the header fields.
+pub(crate) fn parse_fields(input: &str) -> IResult<&str, (Msg<'_>, Header)> {
     map(
         tuple((
-            pri,
+            pri_parts,
             opt(space0),
             timestamp_3164,
-            opt(preceded(tag(" "), hostname)),
-            opt(preceded(tag(" "), tagname)),
+            opt(pair(tag(" "), hostname)),
+            opt(pair(tag(" "), tagname)),
             opt(space0),
             opt(tag(":")),
             opt(space0),
@@ -25,21 +31,80 @@ pub(crate) fn parse(input: &str) -> IResult<&str, Msg> {
             opt(space0),
             rest,
         )),
-        |(pri, s1, timestamp, field1, field2, s2, s3, s4, structured_data, s5, msg)| {
+        |(
+            (facility, severity),
+            s1,
+            timestamp,
+            field1,
+            field2,
+            s2,
+            s3,
+            s4,
+            structured_data,
+            s5,
+            msg,
+        )| {
             let header = format!(
-                "{}{}{}{}{}{}{}{}{}{}",
-                pri,
+                "<{}>{}{}{}{}{}{}{}{}{}{}{}",
+                facility.code() << 3 | severity.code(),
                 s1.unwrap_or_default(),
                 timestamp,
-                field1.unwrap_or_default().unwrap_or_default(),
-                field2.unwrap_or_default().unwrap_or_default(),
+                field1.map(|(s, _)| s).unwrap_or_default(),
+                field1.and_then(|(_, f)| f).unwrap_or_default(),
+                field2.map(|(s, _)| s).unwrap_or_default(),
+                field2.and_then(|(_, f)| f).unwrap_or_default(),
                 s2.unwrap_or_default(),
                 s3.unwrap_or_default(),
                 s4.unwrap_or_default(),
-                structured_data.unwrap_or_default(),
+                structured_data.clone().unwrap_or_default(),
                 s5.unwrap_or_default(),
             );
-            Msg { header, msg }
+            let field1 = field1.and_then(|(_, f)| f);
+            let field2 = field2.and_then(|(_, f)| f);
+            // With a single field before the ':' it is the TAG, e.g. messages
+            // written to /dev/log carry no HOSTNAME.
+            let (hostname, tag) = match (field1, field2) {
+                (Some(_), None) if s3.is_some() => (None, field1),
+                _ => (field1, field2),
+            };
+            let (appname, procid) = tag.map(split_tag).unwrap_or_default();
+            let fields = Header {
+                protocol: Protocol::Rfc3164,
+                facility,
+                severity,
+                version: None,
+                timestamp,
+                hostname: hostname.map(str::to_string),
+                appname,
+                procid,
+                msgid: None,
+                structured_data: structured_data.map(|sd| sd.elements).unwrap_or_default(),
+            };
+            (Msg { header, msg }, fields)
         },
     )(input)
 }
+
+/// Split a TAG like `sshd[1234]` into the program name and process id.
+fn split_tag(tag: &str) -> (Option<String>, Option<String>) {
+    match tag.strip_suffix(']').and_then(|t| t.split_once('[')) {
+        Some((name, pid)) => (Some(name.to_string()), Some(pid.to_string())),
+        None => (Some(tag.to_string()), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        // The header is the input up to the MSG, spaces included. It used
+        // to come out as "<14>Oct 11 22:14:15hostsu[42]: ".
+        let input = "<14>Oct 11 22:14:15 host su[42]: a <b> c";
+        let (_, msg) = parse(input).unwrap();
+        assert_eq!(msg.header, "<14>Oct 11 22:14:15 host su[42]: ");
+        assert_eq!(msg.header.len() + msg.msg.len(), input.len());
+        assert_eq!(msg.msg, "a <b> c");
+    }
+}
@@ -1,22 +1,28 @@
 use crate::format::appname::appname;
 use crate::format::hostname::hostname;
 use crate::format::msgid::msgid;
-use crate::format::pri::pri;
+use crate::format::pri::pri_parts;
 use crate::format::procid::procid;
 use crate::format::structured_data::structured_data;
 use crate::format::timestamp::timestamp_3339;
 use crate::format::version::version;
-use crate::protocol::Msg;
+use crate::message::{Header, Protocol};
+use crate::protocol::{nil, Msg};
 use nom::character::complete::{space0, space1};
 use nom::combinator::{map, rest};
 use nom::sequence::tuple;
 use nom::IResult;
 
 /// Parse the message as per RFC5424
-pub(crate) fn parse(input: &str) -> IResult<&str, Msg> {
+pub(crate) fn parse(input: &str) -> IResult<&str, Msg<'_>> {
+    map(parse_fields, |(msg, _)| msg)(input)
+}
+
+/// `parse`, also returning the header fields.
+pub(crate) fn parse_fields(input: &str) -> IResult<&str, (Msg<'_>, Header)> {
     match map(
         tuple((
-            pri,
+            pri_parts,
             version,
             space1,
             timestamp_3339,
@@ -34,7 +40,7 @@ pub(crate) fn parse(input: &str) -> IResult<&str, Msg> {
             rest,
         )),
         |(
-            pri,
+            (facility, severity),
             version,
             s1,
             timestamp,
@@ -52,8 +58,8 @@ pub(crate) fn parse(input: &str) -> IResult<&str, Msg> {
             rest_msg,
         )| {
             let header = format!(
-                "{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
-                pri,
+                "<{}>{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
+                facility.code() << 3 | severity.code(),
                 version,
                 s1,
                 timestamp,
@@ -69,10 +75,25 @@ pub(crate) fn parse(input: &str) -> IResult<&str, Msg> {
                 structured_data,
                 s7
             );
-            Msg {
-                header,
-                msg: rest_msg,
-            }
+            let fields = Header {
+                protocol: Protocol::Rfc5424,
+                facility,
+                severity,
+                version: version.parse().ok(),
+                timestamp,
+                hostname: nil(hostname),
+                appname: nil(appname),
+                procid: nil(procid),
+                msgid: nil(msgid),
+                structured_data: structured_data.elements,
+            };
+            (
+                Msg {
+                    header,
+                    msg: rest_msg,
+                },
+                fields,
+            )
         },
     )(input)
     {
@@ -0,0 +1,136 @@
+use crate::framing::{FrameDecoder, Framing};
+use crate::stream::MessageResult;
+use std::io::{self, ErrorKind, Read};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Parsed messages from a blocking reader, without framing.
+///
+/// ```no_run
+/// let file = std::fs::File::open("/var/log/messages")?;
+/// for msg in syslog_parse::read_messages(file) {
+///     match msg? {
+///         Ok(msg) => println!("{}", msg.msg),
+///         Err((raw, kind)) => eprintln!("{}: {}", kind, raw),
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_messages<R: Read>(reader: R) -> Messages<R> {
+    Messages::new(reader, Framing::Heuristic)
+}
+
+/// Iterator returned by `read_messages`.
+///
+/// Reads in chunks, and flushes the last message at end of input. An I/O
+/// error is yielded once and ends the iteration.
+#[derive(Debug)]
+pub struct Messages<R> {
+    reader: R,
+    decoder: FrameDecoder,
+    chunk: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> Messages<R> {
+    pub fn new(reader: R, framing: Framing) -> Self {
+        Messages {
+            reader,
+            decoder: FrameDecoder::new(framing),
+            chunk: vec![0; CHUNK_SIZE],
+            done: false,
+        }
+    }
+
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.decoder.set_max_frame_len(len);
+    }
+
+    /// Where the last message returned starts in the input, in bytes.
+    pub fn offset(&self) -> u64 {
+        self.decoder.last_offset()
+    }
+
+    /// The bytes the last message returned was parsed from.
+    pub fn frame(&self) -> &[u8] {
+        self.decoder.last_frame()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for Messages<R> {
+    type Item = io::Result<MessageResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(msg) = self.decoder.next_message() {
+                return Some(Ok(msg));
+            }
+            if self.done {
+                return None;
+            }
+            match self.reader.read(&mut self.chunk) {
+                Ok(0) => {
+                    self.done = true;
+                    self.decoder.finish();
+                }
+                Ok(n) => self.decoder.feed(&self.chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor};
+
+    /// Hands out the input a few bytes at a time, then fails.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::Error::other("gone"));
+            }
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    const INPUT: &str = "<34>Oct 11 22:14:15 mymachine su: 'su root' failed\n<13>1 2003-10-11T22:14:15.003Z host app - - - last";
+
+    #[test]
+    fn test_read_messages() {
+        let msgs: Vec<_> = read_messages(BufReader::new(Cursor::new(INPUT)))
+            .map(|msg| msg.unwrap().unwrap().msg)
+            .collect();
+        assert_eq!(msgs, vec!["'su root' failed\n", "last"]);
+
+        let msgs: Vec<_> = Messages::new(Cursor::new(INPUT), Framing::LF)
+            .map(|msg| msg.unwrap().unwrap().msg)
+            .collect();
+        assert_eq!(msgs, vec!["'su root' failed", "last"]);
+    }
+
+    #[test]
+    fn test_read_error() {
+        let mut msgs = read_messages(Trickle(INPUT.as_bytes()));
+        assert_eq!(
+            msgs.next().unwrap().unwrap().unwrap().msg,
+            "'su root' failed\n"
+        );
+        assert_eq!(msgs.next().unwrap().unwrap_err().to_string(), "gone");
+        assert!(msgs.next().is_none());
+    }
+}
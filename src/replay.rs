@@ -0,0 +1,251 @@
+//! Re-sending captured messages with their original timing.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use syslog_parse::client::{Client, Destination};
+//! use syslog_parse::replay::{read_capture, Replayer, Speed};
+//!
+//! let client = Client::new(Destination::Udp("127.0.0.1:514".parse().unwrap()));
+//! let mut replayer = Replayer::new(client);
+//! replayer.set_speed(Speed::Scaled(10.0));
+//! let stats = replayer.replay(read_capture(File::open("capture.log")?))?;
+//! println!("{} sent", stats.sent);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::client::Sink;
+use crate::clock::{Clock, SystemClock};
+use crate::framing::Framing;
+use crate::message::Message;
+use crate::reader::Messages;
+use crate::stream::MessageResult;
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+/// Messages from a capture file: octet-counted frames, as written by
+/// `syslog-parse listen --capture`. A plain log file is read with
+/// `read_messages` instead.
+pub fn read_capture<R: Read>(reader: R) -> Messages<R> {
+    Messages::new(reader, Framing::OctetCounting)
+}
+
+/// How the gaps between message timestamps are reproduced.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// The gaps as captured.
+    #[default]
+    Original,
+    /// Gaps divided by the factor, so 2.0 replays twice as fast.
+    Scaled(f64),
+    /// No waiting at all.
+    Unlimited,
+}
+
+/// Counters of a `Replayer`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ReplayStats {
+    pub sent: u64,
+    /// Captured messages that did not parse and so were not sent.
+    pub skipped: u64,
+}
+
+/// Sends messages to a `Sink`, sleeping so that they leave with the same
+/// relative timing as their timestamps.
+///
+/// Times are measured from the first message with a timestamp, so delays
+/// in the sink do not add up. Messages without a usable timestamp, or
+/// older than the first one, are sent right away.
+#[derive(Debug)]
+pub struct Replayer<S: Sink> {
+    sink: S,
+    speed: Speed,
+    rewrite_timestamps: bool,
+    clock: Arc<dyn Clock>,
+    start: Option<(Instant, DateTime<FixedOffset>)>,
+    last: Option<DateTime<FixedOffset>>,
+    stats: ReplayStats,
+}
+
+impl<S: Sink> Replayer<S> {
+    pub fn new(sink: S) -> Self {
+        Replayer {
+            sink,
+            speed: Speed::Original,
+            rewrite_timestamps: false,
+            clock: Arc::new(SystemClock),
+            start: None,
+            last: None,
+            stats: ReplayStats::default(),
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+    }
+
+    /// Replace each timestamp with the time it is sent.
+    pub fn set_rewrite_timestamps(&mut self, rewrite: bool) {
+        self.rewrite_timestamps = rewrite;
+    }
+
+    /// Clock for rewritten timestamps, and for the year of the first
+    /// RFC3164 timestamp without one. Later ones take the year closest to
+    /// the previous message's.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn stats(&self) -> ReplayStats {
+        self.stats
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    /// Wait until the message is due, then send it.
+    pub fn send(&mut self, mut msg: Message) -> io::Result<()> {
+        let near = self.last.unwrap_or_else(|| self.clock.now().fixed_offset());
+        if let Some(timestamp) = msg.timestamp_near(near) {
+            self.last = Some(timestamp);
+            self.wait(timestamp);
+        }
+        if self.rewrite_timestamps {
+            let now = self.clock.now();
+            msg.header.timestamp = match DateTime::parse_from_rfc3339(&msg.header.timestamp) {
+                Ok(_) => now.to_rfc3339_opts(SecondsFormat::Micros, false),
+                Err(_) => now.format("%b %e %H:%M:%S").to_string(),
+            };
+        }
+        self.sink.send(&msg)?;
+        self.stats.sent += 1;
+        Ok(())
+    }
+
+    /// Send everything `messages` yields, e.g. from `read_messages`, then
+    /// flush the sink.
+    pub fn replay<I>(&mut self, messages: I) -> io::Result<ReplayStats>
+    where
+        I: IntoIterator<Item = io::Result<MessageResult>>,
+    {
+        for msg in messages {
+            match msg? {
+                Ok(msg) => self.send(msg)?,
+                Err(_) => self.stats.skipped += 1,
+            }
+        }
+        self.sink.flush()?;
+        Ok(self.stats)
+    }
+
+    fn wait(&mut self, timestamp: DateTime<FixedOffset>) {
+        let factor = match self.speed {
+            Speed::Original => 1.0,
+            Speed::Scaled(factor) if factor > 0.0 => factor,
+            _ => return,
+        };
+        let (started, first) = *self.start.get_or_insert((Instant::now(), timestamp));
+        let Ok(offset) = (timestamp - first).to_std() else {
+            return;
+        };
+        let due = started + offset.div_f64(factor);
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Client, Destination};
+    use crate::clock::ManualClock;
+    use crate::read_messages;
+    use crate::server::udp::UdpServer;
+    use chrono::{Local, TimeZone};
+    use std::time::Duration;
+
+    const CAPTURE: &str = "<13>1 2024-03-01T12:00:00Z h app - - - one\n\
+                           garbage\n\
+                           <13>1 2024-03-01T12:00:01Z h app - - - two\n\
+                           <13>1 2024-03-01T12:00:03Z h app - - - three\n";
+
+    #[test]
+    fn test_scaled() {
+        let mut replayer = Replayer::new(Vec::new());
+        replayer.set_speed(Speed::Scaled(20.0));
+        let started = Instant::now();
+        let stats = replayer
+            .replay(Messages::new(CAPTURE.as_bytes(), Framing::LF))
+            .unwrap();
+        // 3 seconds of traffic at 20x
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(
+            stats,
+            ReplayStats {
+                sent: 3,
+                skipped: 1
+            }
+        );
+        let sent = replayer.into_inner();
+        let msgs: Vec<_> = sent.iter().map(|m| m.msg.as_str()).collect();
+        assert_eq!(msgs, ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn test_new_year() {
+        let now = Local.with_ymd_and_hms(2025, 1, 1, 0, 1, 0).unwrap();
+        let mut replayer = Replayer::new(Vec::new());
+        replayer.set_speed(Speed::Scaled(4.0));
+        replayer.set_clock(Arc::new(ManualClock::new(now)));
+        let input = "<13>Dec 31 23:59:59 web1 sshd: one\n\
+                     <13>Jan  1 00:00:01 web1 sshd: two\n";
+        let started = Instant::now();
+        replayer.replay(read_messages(input.as_bytes())).unwrap();
+        // 2 seconds at 4x, not a negative gap
+        assert!(started.elapsed() >= Duration::from_millis(450));
+        assert_eq!(replayer.stats().sent, 2);
+    }
+
+    #[test]
+    fn test_rewrite_timestamps() {
+        let now = Local.with_ymd_and_hms(2025, 6, 7, 8, 9, 10).unwrap();
+        let mut replayer = Replayer::new(Vec::new());
+        replayer.set_speed(Speed::Unlimited);
+        replayer.set_rewrite_timestamps(true);
+        replayer.set_clock(Arc::new(ManualClock::new(now)));
+        let input = "<13>1 2024-03-01T12:00:00Z h app - - - one\n\
+                     <13>Mar  1 12:30:05 web1 sshd: two\n";
+        replayer.replay(read_messages(input.as_bytes())).unwrap();
+        let sent = replayer.into_inner();
+        assert_eq!(
+            sent[0].header.timestamp,
+            now.to_rfc3339_opts(SecondsFormat::Micros, false)
+        );
+        assert_eq!(sent[1].header.timestamp, "Jun  7 08:09:10");
+    }
+
+    #[test]
+    fn test_udp_receiver() {
+        let mut server = UdpServer::bind("127.0.0.1:0").unwrap();
+        let client = Client::new(Destination::Udp(server.local_addr().unwrap()));
+        let mut replayer = Replayer::new(client);
+        replayer.set_speed(Speed::Scaled(100.0));
+        let stats = replayer
+            .replay(Messages::new(CAPTURE.as_bytes(), Framing::LF))
+            .unwrap();
+        assert_eq!(stats.sent, 3);
+        for expected in ["one", "two", "three"] {
+            let received = server.recv().unwrap();
+            assert_eq!(received.message.unwrap().msg, expected);
+        }
+    }
+}
@@ -0,0 +1,404 @@
+//! Sending messages to named outputs by ordered rules.
+//!
+//! ```
+//! use syslog_parse::message::Message;
+//! use syslog_parse::router::{Mode, Route, Router};
+//! use syslog_parse::selector::Selector;
+//! use std::sync::mpsc;
+//!
+//! let (auth, auth_rx) = mpsc::channel();
+//! let (rest, rest_rx) = mpsc::channel();
+//! let mut router = Router::new();
+//! router.set_mode(Mode::FirstMatch);
+//! router.add_output("auth", auth);
+//! router.add_output("rest", rest);
+//!
+//! let selector: Selector = "auth,authpriv.*".parse().unwrap();
+//! let mut route = Route::new("auth", selector);
+//! route.add_output("auth");
+//! router.add_route(route).unwrap();
+//! let mut route = Route::new("rest", |_: &Message| true);
+//! route.add_output("rest");
+//! router.add_route(route).unwrap();
+//!
+//! let msg = Message::parse("<38>Oct 11 22:14:15 host sshd[42]: accepted").unwrap();
+//! assert_eq!(router.route(&msg).unwrap(), 1);
+//! assert!(auth_rx.try_recv().is_ok());
+//! assert!(rest_rx.try_recv().is_err());
+//! ```
+
+use crate::client::Sink;
+use crate::error::UnknownName;
+use crate::filter::Predicate;
+use crate::message::Message;
+use crate::server::{Handler, Received};
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+
+/// Which routes a message goes through.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum Mode {
+    /// Every matching route, up to one that stops.
+    #[default]
+    FanOut,
+    /// Only the first matching route.
+    FirstMatch,
+}
+
+/// Counters of a `Route`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct RouteStats {
+    pub matched: u64,
+    /// Messages written to one of its outputs.
+    pub sent: u64,
+    /// Writes to one of its outputs that failed.
+    pub failed: u64,
+}
+
+/// A rule: the messages matching a predicate go to some outputs.
+pub struct Route {
+    name: String,
+    predicate: Box<dyn Predicate>,
+    outputs: Vec<String>,
+    stop: bool,
+}
+
+impl Route {
+    pub fn new<P: Predicate + 'static>(name: &str, predicate: P) -> Self {
+        Route {
+            name: name.to_string(),
+            predicate: Box::new(predicate),
+            outputs: Vec::new(),
+            stop: false,
+        }
+    }
+
+    /// Send matching messages to the output with this name. A route
+    /// without outputs drops what it matches.
+    pub fn add_output(&mut self, name: &str) {
+        self.outputs.push(name.to_string());
+    }
+
+    /// Skip the routes after this one for messages it matches.
+    pub fn set_stop(&mut self, stop: bool) {
+        self.stop = stop;
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Debug for Route {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Route")
+            .field("name", &self.name)
+            .field("outputs", &self.outputs)
+            .field("stop", &self.stop)
+            .finish()
+    }
+}
+
+struct Compiled {
+    route: Route,
+    outputs: Vec<usize>,
+    stats: RouteStats,
+}
+
+/// Sends each message to the outputs of the routes it matches, tried in
+/// the order they were added.
+///
+/// An output gets a message at most once, even when several matching
+/// routes name it; the first of them counts the send. A failing output
+/// does not keep the message from the others.
+#[derive(Default)]
+pub struct Router {
+    outputs: Vec<(String, Box<dyn Sink>)>,
+    routes: Vec<Compiled>,
+    mode: Mode,
+    unmatched: u64,
+    /// Per output, whether the current message went to it already.
+    done: Vec<bool>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// Add an output, replacing one with the same name.
+    pub fn add_output<S: Sink + 'static>(&mut self, name: &str, sink: S) {
+        match self.outputs.iter_mut().find(|(n, _)| n == name) {
+            Some((_, output)) => *output = Box::new(sink),
+            None => self.outputs.push((name.to_string(), Box::new(sink))),
+        }
+    }
+
+    /// Add a route after the existing ones. Its outputs must have been
+    /// added already.
+    pub fn add_route(&mut self, route: Route) -> Result<(), UnknownName> {
+        let outputs = route
+            .outputs
+            .iter()
+            .map(|name| {
+                self.outputs
+                    .iter()
+                    .position(|(n, _)| n == name)
+                    .ok_or_else(|| UnknownName::new("output", name))
+            })
+            .collect::<Result<_, _>>()?;
+        self.routes.push(Compiled {
+            route,
+            outputs,
+            stats: RouteStats::default(),
+        });
+        Ok(())
+    }
+
+    /// Counters of the route with this name.
+    pub fn stats(&self, route: &str) -> Option<RouteStats> {
+        self.routes
+            .iter()
+            .find(|r| r.route.name == route)
+            .map(|r| r.stats)
+    }
+
+    /// Messages that matched no route.
+    pub fn unmatched(&self) -> u64 {
+        self.unmatched
+    }
+
+    /// Send `msg` on and return how many outputs took it. When outputs
+    /// fail, the first error is returned, after trying all of them.
+    pub fn route(&mut self, msg: &Message) -> io::Result<usize> {
+        self.done.clear();
+        self.done.resize(self.outputs.len(), false);
+        let mut matched = false;
+        let mut sent = 0;
+        let mut error = None;
+        for compiled in &mut self.routes {
+            if !compiled.route.predicate.matches(msg) {
+                continue;
+            }
+            matched = true;
+            compiled.stats.matched += 1;
+            for &output in &compiled.outputs {
+                if std::mem::replace(&mut self.done[output], true) {
+                    continue;
+                }
+                let (name, sink) = &mut self.outputs[output];
+                match sink.send(msg) {
+                    Ok(()) => {
+                        compiled.stats.sent += 1;
+                        sent += 1;
+                    }
+                    Err(e) => {
+                        compiled.stats.failed += 1;
+                        error.get_or_insert_with(|| {
+                            io::Error::new(e.kind(), format!("{}: {}", name, e))
+                        });
+                    }
+                }
+            }
+            if compiled.route.stop || self.mode == Mode::FirstMatch {
+                break;
+            }
+        }
+        if !matched {
+            self.unmatched += 1;
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(sent),
+        }
+    }
+
+    /// Flush every output, returning the first error.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        for (name, sink) in &mut self.outputs {
+            if let Err(e) = sink.flush() {
+                if result.is_ok() {
+                    result = Err(io::Error::new(e.kind(), format!("{}: {}", name, e)));
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Debug for Router {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let outputs: Vec<_> = self.outputs.iter().map(|(name, _)| name).collect();
+        let routes: Vec<_> = self.routes.iter().map(|r| &r.route).collect();
+        f.debug_struct("Router")
+            .field("outputs", &outputs)
+            .field("routes", &routes)
+            .field("mode", &self.mode)
+            .finish()
+    }
+}
+
+/// So routers can be outputs of other routers.
+impl Sink for Router {
+    fn send(&mut self, msg: &Message) -> io::Result<()> {
+        self.route(msg).map(|_| ())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Router::flush(self)
+    }
+}
+
+/// Lets a receiver feed a router directly. Messages that failed to parse
+/// are dropped, and output errors only show in the counters.
+impl Handler for Router {
+    fn handle(&mut self, received: Received) -> bool {
+        if let Ok(msg) = &received.message {
+            let _ = self.route(msg);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::selector::Selector;
+    use std::sync::mpsc::{self, Receiver};
+
+    fn msg(text: &str) -> Message {
+        Message::parse(text).unwrap()
+    }
+
+    fn drain(rx: &Receiver<Message>) -> Vec<String> {
+        rx.try_iter().map(|m| m.msg).collect()
+    }
+
+    fn route<P: Predicate + 'static>(name: &str, predicate: P, outputs: &[&str]) -> Route {
+        let mut route = Route::new(name, predicate);
+        for output in outputs {
+            route.add_output(output);
+        }
+        route
+    }
+
+    fn router(mode: Mode) -> (Router, Receiver<Message>, Receiver<Message>) {
+        let (auth_tx, auth) = mpsc::channel();
+        let (all_tx, all) = mpsc::channel();
+        let mut router = Router::new();
+        router.set_mode(mode);
+        router.add_output("auth", auth_tx);
+        router.add_output("all", all_tx);
+        let selector: Selector = "auth,authpriv.*".parse().unwrap();
+        router
+            .add_route(route("auth", selector, &["auth"]))
+            .unwrap();
+        let filter: Filter = "severity <= err".parse().unwrap();
+        router
+            .add_route(route("errors", filter, &["auth", "all"]))
+            .unwrap();
+        router
+            .add_route(route("all", |_: &Message| true, &["all"]))
+            .unwrap();
+        (router, auth, all)
+    }
+
+    const AUTH_ERR: &str = "<35>Oct 11 22:14:15 host sshd: auth err";
+    const USER_ERR: &str = "<11>Oct 11 22:14:15 host app: user err";
+    const USER_INFO: &str = "<14>Oct 11 22:14:15 host app: user info";
+
+    #[test]
+    fn test_fan_out() {
+        let (mut router, auth, all) = router(Mode::FanOut);
+        assert_eq!(router.route(&msg(AUTH_ERR)).unwrap(), 2);
+        assert_eq!(router.route(&msg(USER_ERR)).unwrap(), 2);
+        assert_eq!(router.route(&msg(USER_INFO)).unwrap(), 1);
+        // each output gets a message once
+        assert_eq!(drain(&auth), ["auth err", "user err"]);
+        assert_eq!(drain(&all), ["auth err", "user err", "user info"]);
+        assert_eq!(
+            router.stats("errors"),
+            Some(RouteStats {
+                matched: 2,
+                sent: 3,
+                failed: 0
+            })
+        );
+        assert_eq!(router.stats("all").unwrap().sent, 1);
+    }
+
+    #[test]
+    fn test_first_match() {
+        let (mut router, auth, all) = router(Mode::FirstMatch);
+        router.route(&msg(AUTH_ERR)).unwrap();
+        router.route(&msg(USER_INFO)).unwrap();
+        assert_eq!(drain(&auth), ["auth err"]);
+        assert_eq!(drain(&all), ["user info"]);
+    }
+
+    #[test]
+    fn test_stop() {
+        let (tx, rx) = mpsc::channel();
+        let mut router = Router::new();
+        router.add_output("out", tx);
+        let mut drop_info = route("drop info", |m: &Message| m.msg.contains("info"), &[]);
+        drop_info.set_stop(true);
+        router.add_route(drop_info).unwrap();
+        router
+            .add_route(route("all", |_: &Message| true, &["out"]))
+            .unwrap();
+        assert_eq!(router.route(&msg(USER_INFO)).unwrap(), 0);
+        assert_eq!(router.route(&msg(AUTH_ERR)).unwrap(), 1);
+        assert_eq!(drain(&rx), ["auth err"]);
+        assert_eq!(router.stats("drop info").unwrap().matched, 1);
+        assert_eq!(router.stats("all").unwrap().matched, 1);
+        assert_eq!(router.unmatched(), 0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut router = Router::new();
+        assert_eq!(
+            router.add_route(route("r", |_: &Message| true, &["nope"])),
+            Err(UnknownName::new("output", "nope"))
+        );
+        let (tx, rx) = mpsc::channel();
+        drop(rx);
+        router.add_output("gone", tx);
+        let (tx, kept) = mpsc::channel();
+        router.add_output("kept", tx);
+        router
+            .add_route(route("r", |_: &Message| true, &["gone", "kept"]))
+            .unwrap();
+        let error = router.route(&msg(USER_INFO)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert!(error.to_string().starts_with("gone: "));
+        let stats = router.stats("r").unwrap();
+        assert_eq!((stats.sent, stats.failed), (1, 1));
+        assert_eq!(drain(&kept), ["user info"]);
+
+        // a router as the output of another one
+        let mut router2 = Router::new();
+        router2.add_output("inner", router);
+        router2
+            .add_route(route(
+                "info",
+                |m: &Message| m.msg.contains("info"),
+                &["inner"],
+            ))
+            .unwrap();
+        let error = router2.route(&msg(USER_INFO)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert!(error.to_string().starts_with("inner: gone: "));
+        assert_eq!(drain(&kept), ["user info"]);
+        assert_eq!(router2.route(&msg(USER_ERR)).unwrap(), 0);
+        assert!(drain(&kept).is_empty());
+        assert_eq!(router2.unmatched(), 1);
+    }
+}
@@ -0,0 +1,209 @@
+//! `/etc/syslog.conf` selectors, e.g. `auth,authpriv.*;kern.warning;mail.none`.
+//!
+//! A selector is a `;` separated list of `facilities.priority` parts,
+//! applied from left to right. Facilities are a `,` separated list or `*`.
+//! The priority is one of:
+//!
+//! - `err`: that severity and the more severe ones
+//! - `=err`: only that severity
+//! - `!err`: removes that severity and the more severe ones
+//! - `!=err`: removes only that severity
+//! - `*`: every severity
+//! - `none`: removes every severity
+//!
+//! so `*.info;mail.none` is everything at info or above except mail.
+//!
+//! As in syslog.conf, `security` means `auth`, and `mark` is accepted but
+//! selects nothing. Elsewhere, e.g. in `Facility::from_str` and filters,
+//! `security` is facility 13.
+
+use crate::error::{ExprError, ExprErrorKind, UnknownName};
+use crate::filter::Predicate;
+use crate::format::pri::split_pri;
+use crate::message::{Facility, Message, Severity};
+use std::ops::Range;
+use std::str::FromStr;
+
+/// A compiled selector: for each facility, the set of severities selected.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Selector {
+    masks: [u8; 24],
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Selector, ExprError> {
+        let mut masks = [0u8; 24];
+        let mut at = 0;
+        for part in selector.split(';') {
+            let start = at + (part.len() - part.trim_start().len());
+            at += part.len() + 1;
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+            let Some((facilities, priority)) = part.rsplit_once('.') else {
+                return Err(ExprError::new(
+                    start + part.len(),
+                    ExprErrorKind::Expected {
+                        expected: ".",
+                        found: "结尾".to_string(),
+                    },
+                ));
+            };
+            let priority_at = start + facilities.len() + 1;
+            let (remove, mask) =
+                priority_mask(priority).map_err(|kind| ExprError::new(priority_at, kind))?;
+
+            let mut offset = start;
+            for facility in facilities.split(',') {
+                let codes = facility_codes(facility.trim())
+                    .map_err(|e| ExprError::new(offset, e.into()))?;
+                for code in codes {
+                    if remove {
+                        masks[code] &= !mask;
+                    } else {
+                        masks[code] |= mask;
+                    }
+                }
+                offset += facility.len() + 1;
+            }
+        }
+        Ok(Selector { masks })
+    }
+
+    pub fn selects(&self, facility: Facility, severity: Severity) -> bool {
+        self.masks[facility.code() as usize] & 1 << severity.code() != 0
+    }
+
+    /// Whether a PRI value is selected. Invalid ones never are.
+    pub fn matches_pri(&self, pri: u8) -> bool {
+        split_pri(pri).is_some_and(|(facility, severity)| self.selects(facility, severity))
+    }
+}
+
+/// Facility codes a name stands for. As in syslog.conf, `security` is the
+/// old name of `auth`, and `mark`, the daemon's own `-- MARK --` lines,
+/// stands for none since no received PRI carries it.
+fn facility_codes(name: &str) -> Result<Range<usize>, UnknownName> {
+    let facility = match name {
+        "*" => return Ok(0..24),
+        "mark" => return Ok(0..0),
+        "security" => Facility::Auth,
+        name => Facility::from_str(name)?,
+    };
+    let code = facility.code() as usize;
+    Ok(code..code + 1)
+}
+
+/// Whether the priority removes severities, and which ones.
+fn priority_mask(priority: &str) -> Result<(bool, u8), ExprErrorKind> {
+    let priority = priority.trim();
+    match priority {
+        "*" => return Ok((false, 0xff)),
+        "none" => return Ok((true, 0xff)),
+        _ => {}
+    }
+    let (remove, priority) = match priority.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, priority),
+    };
+    let (exact, priority) = match priority.strip_prefix('=') {
+        Some(rest) => (true, rest),
+        None => (false, priority),
+    };
+    let severity = Severity::from_str(priority)?;
+    let bit = 1u8 << severity.code();
+    // this severity and all lower codes, i.e. the more severe ones
+    let mask = if exact { bit } else { bit | (bit - 1) };
+    Ok((remove, mask))
+}
+
+impl FromStr for Selector {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, ExprError> {
+        Selector::parse(s)
+    }
+}
+
+impl Predicate for Selector {
+    fn matches(&self, msg: &Message) -> bool {
+        self.selects(msg.header.facility, msg.header.severity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Facility::*;
+    use Severity::*;
+
+    fn selector(s: &str) -> Selector {
+        Selector::parse(s).unwrap_or_else(|e| panic!("{}: {}", s, e))
+    }
+
+    #[test]
+    fn test_selectors() {
+        let s = selector("auth,authpriv.*;kern.warning;mail.none");
+        assert!(s.selects(Auth, Debug));
+        assert!(s.selects(Authpriv, Emerg));
+        assert!(s.selects(Kern, Warning));
+        assert!(s.selects(Kern, Crit));
+        assert!(!s.selects(Kern, Notice));
+        assert!(!s.selects(Mail, Emerg));
+        assert!(!s.selects(User, Emerg));
+
+        let s = selector("*.info;mail.none;authpriv.none");
+        assert!(s.selects(User, Info));
+        assert!(!s.selects(User, Debug));
+        assert!(!s.selects(Mail, Err));
+        assert!(!s.selects(Authpriv, Err));
+
+        let s = selector("mail.=debug");
+        assert!(s.selects(Mail, Debug));
+        assert!(!s.selects(Mail, Info));
+
+        // mail below err only
+        let s = selector("mail.*;mail.!err");
+        assert!(s.selects(Mail, Warning));
+        assert!(!s.selects(Mail, Err));
+        assert!(!s.selects(Mail, Emerg));
+
+        let s = selector("*.*;*.!=notice; cron.none");
+        assert!(s.selects(Daemon, Info));
+        assert!(!s.selects(Daemon, Notice));
+        assert!(!s.selects(Cron, Emerg));
+
+        // deprecated spellings
+        assert_eq!(selector("kern.warn"), selector("kern.warning"));
+        assert_eq!(selector("kern.error"), selector("kern.err"));
+        assert_eq!(selector("security.*"), selector("auth.*"));
+        assert!(!selector("security.*").selects(Security, Emerg));
+        assert_eq!(selector("mark.*"), selector("*.none"));
+        assert_eq!(selector("mark,mail.info"), selector("mail.info"));
+    }
+
+    #[test]
+    fn test_pri_and_messages() {
+        let s = selector("local4.notice");
+        assert!(s.matches_pri(165)); // local4.notice
+        assert!(!s.matches_pri(166)); // local4.info
+        assert!(!s.matches_pri(200));
+        let msg = Message::parse("<165>1 2003-08-24T05:14:15.000003-07:00 h a - - - x").unwrap();
+        assert!(s.matches(&msg));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = Selector::parse("auth.*;kern").unwrap_err();
+        assert_eq!(error.position, 11);
+        let error = Selector::parse("auth,bogus.info").unwrap_err();
+        assert_eq!(error.position, 5);
+        assert_eq!(
+            error.kind,
+            ExprErrorKind::UnknownName(UnknownName::new("facility", "bogus"))
+        );
+        let error = Selector::parse("*.info;mail.loud").unwrap_err();
+        assert_eq!(error.position, 12);
+    }
+}
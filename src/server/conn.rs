@@ -0,0 +1,213 @@
+//! Accept loop and per-connection reading shared by the stream receivers.
+
+use super::{Handler, Peer, Received};
+use crate::clock::{Clock, SystemClock};
+use crate::framing::{FrameDecoder, Framing, DEFAULT_MAX_FRAME_LEN};
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const READ_SIZE: usize = 8 * 1024;
+/// Open connections a receiver accepts by default.
+pub(crate) const DEFAULT_MAX_CONNECTIONS: usize = 1024;
+
+/// Settings common to the stream receivers.
+#[derive(Debug, Clone)]
+pub(crate) struct Options {
+    pub framing: Framing,
+    pub max_frame_len: usize,
+    pub max_connections: usize,
+    pub idle_timeout: Option<Duration>,
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Options {
+    pub fn new(framing: Framing) -> Self {
+        Options {
+            framing,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            idle_timeout: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+pub(crate) trait Listener {
+    type Stream: Socket;
+    type Addr: Send + 'static;
+
+    fn accept(&self) -> io::Result<(Self::Stream, Self::Addr)>;
+
+    /// Something that unblocks `accept`, by connecting to the listener.
+    fn waker(&self) -> io::Result<Box<dyn Fn() + Send + Sync>>;
+}
+
+pub(crate) trait Socket: Read + Send + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+    fn shutdown(&self);
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+/// Turns an accepted socket into the stream to read messages from, and
+/// tells where it comes from. Runs on the connection's thread, under the
+/// idle timeout.
+pub(crate) type Open<S, A> = dyn Fn(S, A) -> io::Result<(Box<dyn Read + Send>, Peer)> + Send + Sync;
+
+struct Shared<H, S> {
+    handler: Mutex<H>,
+    stop: AtomicBool,
+    wake: Box<dyn Fn() + Send + Sync>,
+    connections: Mutex<HashMap<u64, S>>,
+}
+
+/// Accept connections until the handler asks to stop, then close the open
+/// connections and wait for their threads.
+pub(crate) fn serve<L, H>(
+    listener: &L,
+    options: &Options,
+    handler: H,
+    open: Arc<Open<L::Stream, L::Addr>>,
+) -> io::Result<()>
+where
+    L: Listener,
+    H: Handler + 'static,
+{
+    let shared = Arc::new(Shared {
+        handler: Mutex::new(handler),
+        stop: AtomicBool::new(false),
+        wake: listener.waker()?,
+        connections: Mutex::new(HashMap::new()),
+    });
+    let mut threads: Vec<JoinHandle<()>> = Vec::new();
+    let mut next_id = 0u64;
+    let result = loop {
+        let (stream, addr) = match listener.accept() {
+            Ok(conn) => conn,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                ) =>
+            {
+                continue
+            }
+            Err(e) => break Err(e),
+        };
+        if shared.stop.load(Ordering::SeqCst) {
+            break Ok(());
+        }
+        let id = next_id;
+        next_id += 1;
+        threads.retain(|thread| !thread.is_finished());
+        {
+            let mut connections = shared.connections.lock().unwrap();
+            if connections.len() >= options.max_connections {
+                stream.shutdown();
+                continue;
+            }
+            match stream.try_clone() {
+                Ok(clone) => connections.insert(id, clone),
+                Err(_) => continue,
+            };
+        }
+        let mut decoder = FrameDecoder::new(options.framing);
+        decoder.set_max_frame_len(options.max_frame_len);
+        let idle_timeout = options.idle_timeout;
+        let clock = options.clock.clone();
+        let open = open.clone();
+        let shared = shared.clone();
+        threads.push(thread::spawn(move || {
+            let opened = stream
+                .set_read_timeout(idle_timeout)
+                .and_then(|_| open(stream, addr));
+            if let Ok((stream, peer)) = opened {
+                let connection = Connection {
+                    stream,
+                    peer,
+                    decoder,
+                    clock,
+                };
+                connection.run(&shared);
+            }
+            if let Some(stream) = shared.connections.lock().unwrap().remove(&id) {
+                stream.shutdown();
+            }
+        }));
+    };
+    shared.stop();
+    for thread in threads {
+        let _ = thread.join();
+    }
+    result
+}
+
+impl<H: Handler, S: Socket> Shared<H, S> {
+    fn deliver(&self, received: Received) -> bool {
+        if self.stop.load(Ordering::SeqCst) {
+            return false;
+        }
+        let keep_going = self.handler.lock().unwrap().handle(received);
+        if !keep_going {
+            self.stop();
+        }
+        keep_going
+    }
+
+    fn stop(&self) {
+        if self.stop.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        (self.wake)();
+        for stream in self.connections.lock().unwrap().values() {
+            stream.shutdown();
+        }
+    }
+}
+
+struct Connection {
+    stream: Box<dyn Read + Send>,
+    peer: Peer,
+    decoder: FrameDecoder,
+    clock: Arc<dyn Clock>,
+}
+
+impl Connection {
+    fn run<H: Handler, S: Socket>(mut self, shared: &Shared<H, S>) {
+        let mut buf = vec![0; READ_SIZE];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.decoder.feed(&buf[..n]);
+                    if !self.deliver(shared) {
+                        return;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                // idle timeout, or a broken connection
+                Err(_) => break,
+            }
+        }
+        self.decoder.finish();
+        self.deliver(shared);
+    }
+
+    fn deliver<H: Handler, S: Socket>(&mut self, shared: &Shared<H, S>) -> bool {
+        while let Some(message) = self.decoder.next_message() {
+            let received = Received {
+                peer: self.peer.clone(),
+                received_at: self.clock.now(),
+                message,
+                raw: self.decoder.last_frame().to_vec(),
+            };
+            if !shared.deliver(received) {
+                return false;
+            }
+        }
+        true
+    }
+}
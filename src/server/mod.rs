@@ -0,0 +1,124 @@
+//! Blocking syslog receivers.
+//!
+//! Each receiver parses what it reads and hands every message, with where
+//! and when it was received, to a `Handler`: a channel sender or a closure.
+
+mod conn;
+pub mod tcp;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod udp;
+#[cfg(unix)]
+pub mod unix;
+
+use crate::filter::Predicate;
+use crate::stream::MessageResult;
+use chrono::{DateTime, Local};
+use std::fmt::{self, Display, Formatter};
+use std::net::SocketAddr;
+use std::sync::mpsc::{Sender, SyncSender};
+
+/// Where a message came from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Peer {
+    Inet(SocketAddr),
+    /// A TLS client, and the subject of its certificate.
+    Tls {
+        addr: SocketAddr,
+        subject: Option<String>,
+    },
+    /// A local process, identified by the kernel where it can.
+    Unix {
+        credentials: Option<Credentials>,
+    },
+}
+
+/// Who is on the other end of a Unix socket, from `SO_PEERCRED` or
+/// `SCM_CREDENTIALS`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Credentials {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Display for Peer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Peer::Inet(addr) => addr.fmt(f),
+            Peer::Tls {
+                addr,
+                subject: Some(subject),
+            } => write!(f, "{} ({})", addr, subject),
+            Peer::Tls { addr, .. } => addr.fmt(f),
+            Peer::Unix {
+                credentials: Some(c),
+            } => write!(f, "pid={} uid={} gid={}", c.pid, c.uid, c.gid),
+            Peer::Unix { credentials: None } => f.write_str("unix"),
+        }
+    }
+}
+
+/// A message as delivered by a receiver.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Received {
+    pub peer: Peer,
+    pub received_at: DateTime<Local>,
+    pub message: MessageResult,
+    /// The bytes the message was parsed from: the datagram, or the frame
+    /// without its framing.
+    pub raw: Vec<u8>,
+}
+
+/// Consumer of received messages. Returning `false` stops the receiver.
+pub trait Handler: Send {
+    fn handle(&mut self, received: Received) -> bool;
+}
+
+/// Stops once the receiving side is gone.
+impl Handler for Sender<Received> {
+    fn handle(&mut self, received: Received) -> bool {
+        self.send(received).is_ok()
+    }
+}
+
+impl Handler for SyncSender<Received> {
+    fn handle(&mut self, received: Received) -> bool {
+        self.send(received).is_ok()
+    }
+}
+
+/// A closure that never stops the receiver.
+impl<F: FnMut(Received) + Send> Handler for F {
+    fn handle(&mut self, received: Received) -> bool {
+        self(received);
+        true
+    }
+}
+
+/// Passes on only the messages that match a predicate, e.g. a `Filter`.
+/// Messages that failed to parse are dropped.
+#[derive(Debug)]
+pub struct Filtered<P, H> {
+    predicate: P,
+    handler: H,
+}
+
+impl<P: Predicate, H: Handler> Filtered<P, H> {
+    pub fn new(predicate: P, handler: H) -> Self {
+        Filtered { predicate, handler }
+    }
+
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+}
+
+impl<P: Predicate, H: Handler> Handler for Filtered<P, H> {
+    fn handle(&mut self, received: Received) -> bool {
+        match &received.message {
+            Ok(msg) if self.predicate.matches(msg) => self.handler.handle(received),
+            _ => true,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use super::conn::{self, Listener, Open, Options, Socket};
+use super::{Handler, Peer};
+use crate::clock::Clock;
+use crate::framing::Framing;
+use std::io::{self, Read};
+use std::net::{Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Stream receiver, one thread and one `FrameDecoder` per connection.
+///
+/// All connections deliver to the same handler. A connection that stays
+/// silent for the idle timeout is closed; the message it was sending when
+/// it closed, for whatever reason, is delivered as it is.
+#[derive(Debug)]
+pub struct TcpServer {
+    listener: TcpListener,
+    options: Options,
+}
+
+impl TcpServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        TcpListener::bind(addr).map(TcpServer::from_listener)
+    }
+
+    pub fn from_listener(listener: TcpListener) -> Self {
+        TcpServer {
+            listener,
+            options: Options::new(Framing::default()),
+        }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn set_framing(&mut self, framing: Framing) {
+        self.options.framing = framing;
+    }
+
+    /// Longest frame, 64 KiB by default, whatever the framing.
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.options.max_frame_len = len;
+    }
+
+    /// Connections over the limit, 1024 by default, are closed as soon as
+    /// they are accepted.
+    pub fn set_max_connections(&mut self, max: usize) {
+        self.options.max_connections = max;
+    }
+
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.options.idle_timeout = timeout;
+    }
+
+    /// Clock for the `received_at` timestamps.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.options.clock = clock;
+    }
+
+    /// Accept connections until the handler asks to stop, then close the
+    /// open connections and wait for their threads.
+    pub fn run<H: Handler + 'static>(&self, handler: H) -> io::Result<()> {
+        self.serve(
+            handler,
+            Arc::new(|stream, addr| {
+                Ok((Box::new(stream) as Box<dyn Read + Send>, Peer::Inet(addr)))
+            }),
+        )
+    }
+
+    pub(crate) fn serve<H: Handler + 'static>(
+        &self,
+        handler: H,
+        open: Arc<Open<TcpStream, SocketAddr>>,
+    ) -> io::Result<()> {
+        conn::serve(&self.listener, &self.options, handler, open)
+    }
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+    type Addr = SocketAddr;
+
+    fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        TcpListener::accept(self)
+    }
+
+    fn waker(&self) -> io::Result<Box<dyn Fn() + Send + Sync>> {
+        let mut addr = self.local_addr()?;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr {
+                SocketAddr::V4(_) => [127, 0, 0, 1].into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        Ok(Box::new(move || {
+            let _ = TcpStream::connect(addr);
+        }))
+    }
+}
+
+impl Socket for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+
+    fn shutdown(&self) {
+        let _ = TcpStream::shutdown(self, Shutdown::Both);
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Received;
+    use std::io::Write;
+    use std::sync::mpsc;
+    use std::thread;
+
+    const MSG1: &str = "<34>Oct 11 22:14:15 mymachine su: 'su root' failed";
+    const MSG2: &str = "<13>1 2003-10-11T22:14:15.003Z host app - - - partial";
+
+    fn msg(received: &Received) -> &str {
+        &received.message.as_ref().unwrap().msg
+    }
+
+    #[test]
+    fn test_tcp_server() {
+        let mut server = TcpServer::bind("127.0.0.1:0").unwrap();
+        server.set_framing(Framing::OctetCounting);
+        server.set_max_connections(2);
+        let addr = server.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::spawn(move || server.run(tx));
+
+        let mut first = TcpStream::connect(addr).unwrap();
+        let mut second = TcpStream::connect(addr).unwrap();
+        let mut out = Vec::new();
+        Framing::OctetCounting.encode(MSG1, &mut out).unwrap();
+        first.write_all(&out).unwrap();
+        let received = rx.recv().unwrap();
+        assert_eq!(received.peer, Peer::Inet(first.local_addr().unwrap()));
+        assert_eq!(msg(&received), "'su root' failed");
+        assert_eq!(received.raw, MSG1.as_bytes());
+
+        // over the limit
+        let mut third = TcpStream::connect(addr).unwrap();
+        assert_eq!(third.read(&mut [0; 1]).unwrap(), 0);
+
+        // the partial frame is delivered on disconnect
+        write!(second, "100 {}", MSG2).unwrap();
+        second.shutdown(Shutdown::Write).unwrap();
+        let received = rx.recv().unwrap();
+        assert_eq!(received.peer, Peer::Inet(second.local_addr().unwrap()));
+        assert_eq!(msg(&received), "partial");
+
+        drop(rx);
+        first.write_all(&out).unwrap();
+        handle.join().unwrap().unwrap();
+        assert_eq!(first.read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let mut server = TcpServer::bind("127.0.0.1:0").unwrap();
+        server.set_idle_timeout(Some(Duration::from_millis(50)));
+        let addr = server.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || server.run(tx));
+
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(MSG1.as_bytes()).unwrap();
+        assert_eq!(msg(&rx.recv().unwrap()), "'su root' failed");
+        assert_eq!(client.read(&mut [0; 1]).unwrap(), 0);
+    }
+}
@@ -0,0 +1,123 @@
+use super::tcp::TcpServer;
+use super::{Handler, Peer};
+use crate::clock::Clock;
+use crate::framing::Framing;
+use crate::tls::subject;
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::io::{self, Read};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// RFC5425 receiver: TLS with octet counting framing.
+///
+/// Otherwise it behaves as `TcpServer`. Messages come from `Peer::Tls`,
+/// with the subject of the client certificate when one was presented.
+#[derive(Debug)]
+pub struct TlsServer {
+    tcp: TcpServer,
+    config: Arc<ServerConfig>,
+}
+
+impl TlsServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A, config: Arc<ServerConfig>) -> io::Result<Self> {
+        Ok(TlsServer::from_listener(TcpListener::bind(addr)?, config))
+    }
+
+    pub fn from_listener(listener: TcpListener, config: Arc<ServerConfig>) -> Self {
+        let mut tcp = TcpServer::from_listener(listener);
+        tcp.set_framing(Framing::OctetCounting);
+        TlsServer { tcp, config }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp.local_addr()
+    }
+
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.tcp.set_max_frame_len(len);
+    }
+
+    pub fn set_max_connections(&mut self, max: usize) {
+        self.tcp.set_max_connections(max);
+    }
+
+    /// Also bounds the handshake.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.tcp.set_idle_timeout(timeout);
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.tcp.set_clock(clock);
+    }
+
+    /// Accept connections until the handler asks to stop. Connections that
+    /// fail the handshake are closed without delivering anything.
+    pub fn run<H: Handler + 'static>(&self, handler: H) -> io::Result<()> {
+        let config = self.config.clone();
+        self.tcp.serve(
+            handler,
+            Arc::new(move |mut stream: TcpStream, addr| {
+                let mut conn = ServerConnection::new(config.clone()).map_err(io::Error::other)?;
+                while conn.is_handshaking() {
+                    conn.complete_io(&mut stream)?;
+                }
+                let subject = conn
+                    .peer_certificates()
+                    .and_then(|certs| certs.first())
+                    .and_then(subject);
+                Ok((
+                    Box::new(StreamOwned::new(conn, stream)) as Box<dyn Read + Send>,
+                    Peer::Tls { addr, subject },
+                ))
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tls::TlsClient;
+    use crate::message::Message;
+    use crate::tls::tests::pki;
+    use crate::tls::{client_config, server_config};
+    use std::sync::mpsc;
+    use std::thread;
+
+    const MSG: &str =
+        "<34>1 2003-10-11T22:14:15.003Z mymachine su - ID47 - 'su root' failed\nwith a newline";
+
+    #[test]
+    fn test_mutual_tls() {
+        let pki = pki();
+        let config = server_config(pki.server.0, pki.server.1, Some(pki.roots.clone())).unwrap();
+        let server = TlsServer::bind("127.0.0.1:0", config).unwrap();
+        let addr = server.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || server.run(tx));
+
+        // without a client certificate nothing gets through
+        let anonymous = client_config(pki.roots.clone(), None).unwrap();
+        if let Ok(mut client) = TlsClient::connect(addr, "localhost", anonymous) {
+            let _ = client.send_str("<13>1 - - - - - - anonymous");
+            let _ = client.close();
+        }
+
+        let config = client_config(pki.roots, Some(pki.client)).unwrap();
+        let mut client = TlsClient::connect(addr, "localhost", config).unwrap();
+        let msg = Message::parse(MSG).unwrap();
+        client.send(&msg).unwrap();
+        client.send(&msg).unwrap();
+        client.close().unwrap();
+
+        for _ in 0..2 {
+            let received = rx.recv().unwrap();
+            assert_eq!(received.message, Ok(msg.clone()));
+            match received.peer {
+                Peer::Tls { subject, .. } => assert_eq!(subject.as_deref(), Some("CN=client1")),
+                peer => panic!("{:?}", peer),
+            }
+        }
+    }
+}
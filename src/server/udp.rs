@@ -0,0 +1,189 @@
+use super::{Handler, Peer, Received};
+use crate::clock::{Clock, SystemClock};
+use crate::message::Message;
+use crate::stream::MessageResult;
+use socket2::SockRef;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+
+/// Largest datagram accepted by default, the largest UDP payload over IPv4.
+pub const DEFAULT_MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// RFC5426 receiver: each datagram is one message.
+///
+/// A trailing LF, CRLF or NUL, which many senders add, is not part of the
+/// message. A datagram longer than the maximum size is cut, and parsed
+/// messages are then marked `truncated`.
+#[derive(Debug)]
+pub struct UdpServer {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+    max_datagram_size: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl UdpServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        UdpSocket::bind(addr).map(UdpServer::from_socket)
+    }
+
+    pub fn from_socket(socket: UdpSocket) -> Self {
+        UdpServer {
+            socket,
+            buf: Vec::new(),
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Set the kernel receive buffer (`SO_RCVBUF`), to ride out bursts.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        SockRef::from(&self.socket).set_recv_buffer_size(size)
+    }
+
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        SockRef::from(&self.socket).recv_buffer_size()
+    }
+
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.max_datagram_size = size;
+    }
+
+    /// Clock for the `received_at` timestamps.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Wait for one datagram.
+    pub fn recv(&mut self) -> io::Result<Received> {
+        // one spare byte tells an oversized datagram from one of exactly the maximum size
+        self.buf.resize(self.max_datagram_size + 1, 0);
+        let (n, addr) = self.socket.recv_from(&mut self.buf)?;
+        let received_at = self.clock.now();
+        let truncated = n > self.max_datagram_size;
+        let datagram = &self.buf[..n.min(self.max_datagram_size)];
+        Ok(Received {
+            peer: Peer::Inet(addr),
+            received_at,
+            message: parse_datagram(datagram, truncated),
+            raw: datagram.to_vec(),
+        })
+    }
+
+    /// Receive until the handler asks to stop.
+    pub fn run<H: Handler>(&mut self, mut handler: H) -> io::Result<()> {
+        loop {
+            let received = match self.recv() {
+                Ok(received) => received,
+                // ICMP port unreachable from an earlier send, on some platforms
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if !handler.handle(received) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+pub(super) fn parse_datagram(datagram: &[u8], truncated: bool) -> MessageResult {
+    let mut datagram = datagram;
+    if !truncated {
+        datagram = datagram.strip_suffix(b"\0").unwrap_or(datagram);
+        datagram = datagram.strip_suffix(b"\n").unwrap_or(datagram);
+        datagram = datagram.strip_suffix(b"\r").unwrap_or(datagram);
+    }
+    let raw = String::from_utf8_lossy(datagram);
+    match Message::parse(&raw) {
+        Ok(msg) => Ok(Message { truncated, ..msg }),
+        Err(kind) => Err((raw.into_owned(), kind)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::error::ErrorKind;
+    use crate::filter::Filter;
+    use crate::server::Filtered;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn test_udp_server() {
+        let mut server = UdpServer::bind("127.0.0.1:0").unwrap();
+        let clock = ManualClock::default();
+        server.set_clock(Arc::new(clock.clone()));
+        server.set_max_datagram_size(64);
+        server.set_recv_buffer_size(1 << 16).unwrap();
+        assert!(server.recv_buffer_size().unwrap() >= 1 << 16);
+        let addr = server.local_addr().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::spawn(move || server.run(tx));
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let datagrams: [&[u8]; 4] = [
+            b"<34>Oct 11 22:14:15 mymachine su: 'su root' failed\n",
+            // a second header in the same datagram is part of the message
+            b"<13>1 2003-10-11T22:14:15.003Z host app - - - a <14>b",
+            b"no header",
+            b"<13>1 2003-10-11T22:14:15.003Z host app - - - this one is too long",
+        ];
+        for datagram in datagrams {
+            client.send_to(datagram, addr).unwrap();
+        }
+
+        let received: Vec<Received> = rx.iter().take(4).collect();
+        for r in &received {
+            assert_eq!(r.peer, Peer::Inet(client.local_addr().unwrap()));
+            assert_eq!(r.received_at, clock.now());
+        }
+        let msg = |i: usize| received[i].message.as_ref().unwrap();
+        assert_eq!(msg(0).msg, "'su root' failed");
+        assert_eq!(received[0].raw, datagrams[0]);
+        assert_eq!(msg(1).msg, "a <14>b");
+        assert_eq!(
+            received[2].message,
+            Err(("no header".to_string(), ErrorKind::Header))
+        );
+        assert_eq!(msg(3).msg, "this one is too lo");
+        assert!(msg(3).truncated);
+
+        // the receiver stops once the channel is gone
+        drop(rx);
+        client.send_to(b"bye", addr).unwrap();
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_filtered() {
+        let mut server = UdpServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let filter: Filter = "severity <= warning".parse().unwrap();
+        thread::spawn(move || server.run(Filtered::new(filter, tx)));
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for datagram in [
+            "<14>Oct 11 22:14:15 h a: info",
+            "bad",
+            "<12>Oct 11 22:14:15 h a: warn",
+        ] {
+            client.send_to(datagram.as_bytes(), addr).unwrap();
+        }
+        let received = rx.recv().unwrap();
+        assert_eq!(received.message.unwrap().msg, "warn");
+    }
+}
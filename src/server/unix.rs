@@ -0,0 +1,323 @@
+//! Receivers for local sockets such as `/dev/log`.
+//!
+//! Local clients usually send `<PRI>TIMESTAMP TAG: MSG`, without a hostname,
+//! which the RFC3164 parser accepts. On Linux each message carries the
+//! sender's pid, uid and gid.
+
+use super::conn::{self, Listener, Options, Socket};
+use super::udp::{parse_datagram, DEFAULT_MAX_DATAGRAM_SIZE};
+use super::{Credentials, Handler, Peer, Received};
+use crate::clock::{Clock, SystemClock};
+use crate::framing::Framing;
+use socket2::SockRef;
+use std::io::{self, Read};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixDatagram, UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Datagram receiver: each datagram is one message, as with `UdpServer`.
+#[derive(Debug)]
+pub struct UnixDatagramServer {
+    socket: UnixDatagram,
+    buf: Vec<u8>,
+    max_datagram_size: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl UnixDatagramServer {
+    /// Bind a socket at `path`, which must not exist yet.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        UnixDatagram::bind(path).and_then(UnixDatagramServer::from_socket)
+    }
+
+    pub fn from_socket(socket: UnixDatagram) -> io::Result<Self> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        nix::sys::socket::setsockopt(&socket, nix::sys::socket::sockopt::PassCred, &true)?;
+        Ok(UnixDatagramServer {
+            socket,
+            buf: Vec::new(),
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    pub fn socket(&self) -> &UnixDatagram {
+        &self.socket
+    }
+
+    /// Set the kernel receive buffer (`SO_RCVBUF`), to ride out bursts.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        SockRef::from(&self.socket).set_recv_buffer_size(size)
+    }
+
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        self.max_datagram_size = size;
+    }
+
+    /// Clock for the `received_at` timestamps.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Wait for one datagram.
+    pub fn recv(&mut self) -> io::Result<Received> {
+        self.buf.resize(self.max_datagram_size + 1, 0);
+        let (n, credentials) = self.recv_with_credentials()?;
+        let received_at = self.clock.now();
+        let truncated = n > self.max_datagram_size;
+        let datagram = &self.buf[..n.min(self.max_datagram_size)];
+        Ok(Received {
+            peer: Peer::Unix { credentials },
+            received_at,
+            message: parse_datagram(datagram, truncated),
+            raw: datagram.to_vec(),
+        })
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn recv_with_credentials(&mut self) -> io::Result<(usize, Option<Credentials>)> {
+        use nix::sys::socket::{recvmsg, ControlMessageOwned, MsgFlags, UnixAddr, UnixCredentials};
+        use std::io::IoSliceMut;
+        use std::os::fd::AsRawFd;
+
+        let mut cmsg = nix::cmsg_space!(UnixCredentials);
+        let mut iov = [IoSliceMut::new(&mut self.buf)];
+        let msg = recvmsg::<UnixAddr>(
+            self.socket.as_raw_fd(),
+            &mut iov,
+            Some(&mut cmsg),
+            MsgFlags::empty(),
+        )?;
+        let credentials = msg.cmsgs().ok().and_then(|mut cmsgs| {
+            cmsgs.find_map(|cmsg| match cmsg {
+                ControlMessageOwned::ScmCredentials(c) => Some(Credentials {
+                    pid: c.pid(),
+                    uid: c.uid(),
+                    gid: c.gid(),
+                }),
+                _ => None,
+            })
+        });
+        Ok((msg.bytes, credentials))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn recv_with_credentials(&mut self) -> io::Result<(usize, Option<Credentials>)> {
+        self.socket.recv(&mut self.buf).map(|n| (n, None))
+    }
+
+    /// Receive until the handler asks to stop.
+    pub fn run<H: Handler>(&mut self, mut handler: H) -> io::Result<()> {
+        loop {
+            let received = match self.recv() {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if !handler.handle(received) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Stream receiver, with the same connection handling as `TcpServer`.
+///
+/// Messages are taken to end with a NUL byte, as glibc's `syslog()` sends
+/// them over stream sockets; see `set_framing`.
+#[derive(Debug)]
+pub struct UnixStreamServer {
+    listener: UnixListener,
+    options: Options,
+}
+
+impl UnixStreamServer {
+    /// Bind a socket at `path`, which must not exist yet.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        UnixListener::bind(path).map(UnixStreamServer::from_listener)
+    }
+
+    pub fn from_listener(listener: UnixListener) -> Self {
+        UnixStreamServer {
+            listener,
+            options: Options::new(Framing::NonTransparent(0)),
+        }
+    }
+
+    pub fn set_framing(&mut self, framing: Framing) {
+        self.options.framing = framing;
+    }
+
+    /// Longest frame, 64 KiB by default, whatever the framing.
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.options.max_frame_len = len;
+    }
+
+    /// Connections over the limit, 1024 by default, are closed as soon as
+    /// they are accepted.
+    pub fn set_max_connections(&mut self, max: usize) {
+        self.options.max_connections = max;
+    }
+
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.options.idle_timeout = timeout;
+    }
+
+    /// Clock for the `received_at` timestamps.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.options.clock = clock;
+    }
+
+    /// Accept connections until the handler asks to stop, then close the
+    /// open connections and wait for their threads.
+    pub fn run<H: Handler + 'static>(&self, handler: H) -> io::Result<()> {
+        conn::serve(
+            &self.listener,
+            &self.options,
+            handler,
+            Arc::new(|stream: UnixStream, ()| {
+                let credentials = peer_credentials(&stream);
+                Ok((
+                    Box::new(stream) as Box<dyn Read + Send>,
+                    Peer::Unix { credentials },
+                ))
+            }),
+        )
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_credentials(stream: &UnixStream) -> Option<Credentials> {
+    use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+
+    getsockopt(stream, PeerCredentials)
+        .ok()
+        .map(|c| Credentials {
+            pid: c.pid(),
+            uid: c.uid(),
+            gid: c.gid(),
+        })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_credentials(_: &UnixStream) -> Option<Credentials> {
+    None
+}
+
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+    type Addr = ();
+
+    fn accept(&self) -> io::Result<(UnixStream, ())> {
+        UnixListener::accept(self).map(|(stream, _)| (stream, ()))
+    }
+
+    fn waker(&self) -> io::Result<Box<dyn Fn() + Send + Sync>> {
+        let path = self
+            .local_addr()?
+            .as_pathname()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "listener is not bound to a path",
+                )
+            })?;
+        Ok(Box::new(move || {
+            let _ = UnixStream::connect(&path);
+        }))
+    }
+}
+
+impl Socket for UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        UnixStream::try_clone(self)
+    }
+
+    fn shutdown(&self) {
+        let _ = UnixStream::shutdown(self, Shutdown::Both);
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Protocol;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::sync::mpsc;
+    use std::thread;
+
+    const LOCAL: &str = "<13>Oct 11 22:14:15 myapp[123]: hello";
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("syslog-parse-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// This process, as the kernel reports it to the server.
+    fn me() -> Option<Credentials> {
+        use std::os::unix::fs::MetadataExt;
+
+        if !cfg!(any(target_os = "linux", target_os = "android")) {
+            return None;
+        }
+        let proc = std::fs::metadata("/proc/self").unwrap();
+        Some(Credentials {
+            pid: std::process::id() as i32,
+            uid: proc.uid(),
+            gid: proc.gid(),
+        })
+    }
+
+    fn check(received: &Received) {
+        assert_eq!(received.peer, Peer::Unix { credentials: me() });
+        let msg = received.message.as_ref().unwrap();
+        assert_eq!(msg.header.protocol, Protocol::Rfc3164);
+        assert_eq!(msg.header.hostname, None);
+        assert_eq!(msg.header.appname.as_deref(), Some("myapp"));
+        assert_eq!(msg.header.procid.as_deref(), Some("123"));
+        assert_eq!(msg.msg, "hello");
+    }
+
+    #[test]
+    fn test_datagram() {
+        let path = socket_path("dgram");
+        let mut server = UnixDatagramServer::bind(&path).unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || server.run(tx));
+
+        let client = UnixDatagram::unbound().unwrap();
+        client.send_to(LOCAL.as_bytes(), &path).unwrap();
+        check(&rx.recv().unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stream() {
+        let path = socket_path("stream");
+        let server = UnixStreamServer::bind(&path).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::spawn(move || server.run(tx));
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        write!(client, "{}\0{}", LOCAL, LOCAL).unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        check(&rx.recv().unwrap());
+        check(&rx.recv().unwrap());
+
+        drop(rx);
+        let mut client = UnixStream::connect(&path).unwrap();
+        write!(client, "{}\0", LOCAL).unwrap();
+        handle.join().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,15 +1,103 @@
-use crate::protocol::single_parse;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{ErrorKind, NomErrorKind};
+use crate::format::pri::could_be_pri;
+use crate::message::{Header, Message};
+use crate::protocol::parse_fields;
 use chrono::{DateTime, Duration, Local};
-use nom::bytes::complete::take_until;
-use nom::error::{Error, ParseError};
+use memchr::memchr;
+use nom::error::ParseError;
+use std::collections::VecDeque;
+use std::sync::Arc;
 
 const DEFAULT_MAX_SIZE: usize = 1000;
+const DEFAULT_FLUSH_TIMEOUT_MS: i64 = 500;
 
+/// What `MsgStream` does when a message or the pending buffer goes over
+/// its byte limit.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Keep the bytes up to the limit, discard the rest and mark the
+    /// message as truncated.
+    Truncate,
+    /// Discard the whole message.
+    Drop,
+    /// Discard the message and report `ErrorKind::TooLarge`.
+    Error,
+}
+
+/// How often each `OverflowPolicy` was applied.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct OverflowStats {
+    pub truncated: u64,
+    pub dropped: u64,
+    pub errors: u64,
+}
+
+/// A parsed message, or the raw text of a segment that has no valid header.
+pub type MessageResult = Result<Message, (String, ErrorKind)>;
+
+/// A message emitted by [`MsgStream::flush`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Flushed {
+    pub msg: String,
+    /// Whether the message starts with a valid syslog header and does not
+    /// end in the middle of a UTF-8 character.
+    pub complete: bool,
+    /// Whether the message went over `max_message_bytes` under
+    /// `OverflowPolicy::Error`; `msg` has the bytes up to the limit.
+    pub too_large: bool,
+}
+
+/// A message cut out of the stream. The header is kept from when the
+/// boundary was found, together with its length in `raw`.
+#[derive(Debug)]
+struct Segment {
+    raw: String,
+    header: Option<(Header, usize)>,
+    overflow: Option<OverflowPolicy>,
+}
+
+impl Segment {
+    fn into_message(self) -> MessageResult {
+        match (self.header, self.overflow) {
+            (_, Some(OverflowPolicy::Error)) => Err((self.raw, ErrorKind::TooLarge)),
+            (Some((header, len)), overflow) => Ok(Message {
+                header,
+                msg: self.raw.get(len..).unwrap_or_default().to_string(),
+                truncated: overflow.is_some(),
+            }),
+            (None, _) => Err((self.raw, ErrorKind::Header)),
+        }
+    }
+
+    fn is_error(&self) -> bool {
+        self.overflow == Some(OverflowPolicy::Error)
+    }
+}
+
+/// Splits a byte stream into messages at each header.
+///
+/// Kept for compatibility only: every call joins the pending bytes with
+/// the new chunk, decodes them and parses partial headers again, so a
+/// message that arrives over many small reads costs quadratic time. New
+/// code should use `parser::StreamParser` or `framing::FrameDecoder`.
 #[derive(Debug)]
 pub struct MsgStream {
     pub last_msg: String,
     pub res: Vec<u8>,
+    last_header: Option<(Header, usize)>,
+    /// Set once the current message went over `max_message_bytes`; the
+    /// rest of it is discarded until the next header.
+    last_overflow: Option<OverflowPolicy>,
+    /// Segments cut from the input but not yet returned.
+    ready: VecDeque<Segment>,
     max_size: usize,
+    max_message_bytes: usize,
+    max_pending_bytes: usize,
+    overflow_policy: OverflowPolicy,
+    overflow_stats: OverflowStats,
+    flush_timeout: Duration,
+    clock: Arc<dyn Clock>,
     last_parse_time: DateTime<Local>,
 }
 
@@ -18,7 +106,16 @@ impl Default for MsgStream {
         MsgStream {
             last_msg: "".to_string(),
             res: vec![],
+            last_header: None,
+            last_overflow: None,
+            ready: VecDeque::new(),
             max_size: DEFAULT_MAX_SIZE,
+            max_message_bytes: usize::MAX,
+            max_pending_bytes: usize::MAX,
+            overflow_policy: OverflowPolicy::Truncate,
+            overflow_stats: OverflowStats::default(),
+            flush_timeout: Duration::milliseconds(DEFAULT_FLUSH_TIMEOUT_MS),
+            clock: Arc::new(SystemClock),
             last_parse_time: Local::now(),
         }
     }
@@ -29,111 +126,339 @@ impl MsgStream {
         self.max_size = size;
     }
 
+    /// Upper bound in bytes for a single message. Unlimited by default.
+    pub fn set_max_message_bytes(&mut self, size: usize) {
+        self.max_message_bytes = size;
+    }
+
+    /// Upper bound in bytes for input that is held back unsplit, e.g. after
+    /// `max_size` messages were returned. Unlimited by default.
+    pub fn set_max_pending_bytes(&mut self, size: usize) {
+        self.max_pending_bytes = size;
+    }
+
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.overflow_policy = policy;
+    }
+
+    pub fn overflow_stats(&self) -> OverflowStats {
+        self.overflow_stats
+    }
+
+    /// How long the stream waits without new input before `next` emits
+    /// the pending message.
+    pub fn set_flush_timeout(&mut self, timeout: Duration) {
+        self.flush_timeout = timeout;
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.last_parse_time = clock.now();
+        self.clock = clock;
+    }
+
     pub fn clear(&mut self) {
         self.last_msg.clear();
         self.res.clear();
+        self.last_header = None;
+        self.last_overflow = None;
+        self.ready.clear();
     }
 
+    /// Cut complete messages out of the input. With `OverflowPolicy::Error`
+    /// an oversized message fails the call that finds it; messages cut
+    /// before it stay queued for the following call.
     pub fn multi_parse<'a, T: ParseError<&'a str>>(
         &mut self,
         value: Vec<u8>,
     ) -> Result<Vec<String>, T> {
-        self.last_parse_time = Local::now();
-        let next_msg = if self.last_msg.ends_with('<') {
-            self.last_msg.pop();
-            ["<".as_bytes().to_vec(), self.res.clone(), value].concat()
-        } else {
-            [self.res.clone(), value].concat()
-        };
-        let next_msg = String::from_utf8_lossy(&next_msg);
-        let mut next_msg = next_msg.as_ref();
+        self.last_parse_time = self.clock.now();
+        self.fill(&value);
+        if let Some(i) = self.ready.iter().position(Segment::is_error) {
+            self.ready.remove(i);
+            return Err(T::from_error_kind("", NomErrorKind::TooLarge));
+        }
         let mut logs = Vec::new();
+        while logs.len() < self.max_size && self.ready.front().is_some_and(|s| !s.is_error()) {
+            logs.extend(self.ready.pop_front().map(|s| s.raw));
+        }
+        Ok(logs)
+    }
+
+    /// Like `multi_parse`, but yields parsed messages. The header of each
+    /// message is parsed only once, when its boundary is found.
+    pub fn multi_parse_messages(&mut self, value: Vec<u8>) -> Vec<MessageResult> {
+        self.last_parse_time = self.clock.now();
+        self.fill(&value);
+        let count = self.max_size.min(self.ready.len());
+        self.ready
+            .drain(..count)
+            .map(Segment::into_message)
+            .collect()
+    }
+
+    /// Like `next`, but yields a parsed message.
+    pub fn next_message(&mut self) -> Option<MessageResult> {
+        self.next_segment().map(Segment::into_message)
+    }
+
+    /// Emit everything still pending, for use at end of input (EOF or a
+    /// closed connection) where the idle-flush timer in `next` never fires.
+    /// The stream is empty afterwards.
+    pub fn flush(&mut self) -> Vec<Flushed> {
+        self.flush_segments()
+            .into_iter()
+            .map(|(segment, complete)| Flushed {
+                complete: complete && segment.overflow.is_none(),
+                too_large: segment.is_error(),
+                msg: segment.raw,
+            })
+            .collect()
+    }
+
+    /// Like `flush`, but yields parsed messages.
+    pub fn flush_messages(&mut self) -> Vec<MessageResult> {
+        self.flush_segments()
+            .into_iter()
+            .map(|(segment, _)| segment.into_message())
+            .collect()
+    }
+
+    fn flush_segments(&mut self) -> Vec<(Segment, bool)> {
+        let segments = self.split(&[], usize::MAX);
+        self.ready.extend(segments);
+        let mut segments: Vec<(Segment, bool)> = self
+            .ready
+            .drain(..)
+            .map(|segment| {
+                let complete = segment.header.is_some();
+                (segment, complete)
+            })
+            .collect();
+        if !self.last_msg.is_empty() || !self.res.is_empty() {
+            let valid_utf8 = std::str::from_utf8(&self.res).is_ok();
+            let segment = Segment {
+                raw: format!("{}{}", self.last_msg, String::from_utf8_lossy(&self.res)),
+                header: self.last_header.take(),
+                overflow: self.last_overflow.take(),
+            };
+            let complete = valid_utf8 && segment.header.is_some();
+            segments.push((segment, complete));
+        }
+        self.clear();
+        segments
+    }
+
+    fn next_segment(&mut self) -> Option<Segment> {
+        let now = self.clock.now();
+        if self.ready.is_empty() {
+            self.fill(&[]);
+        }
+        if let Some(segment) = self.ready.pop_front() {
+            return Some(segment);
+        }
+
+        let interval = now.signed_duration_since(self.last_parse_time);
+        if interval.ge(&self.flush_timeout) && !self.last_msg.is_empty() {
+            let segment = Segment {
+                raw: format!("{}{}", self.last_msg, String::from_utf8_lossy(&self.res)),
+                header: self.last_header.take(),
+                overflow: self.last_overflow.take(),
+            };
+            self.clear();
+            return Some(segment);
+        }
+        None
+    }
+
+    /// Split `value` into the ready queue, up to `max_size` queued messages.
+    fn fill(&mut self, value: &[u8]) {
+        let limit = self.max_size.saturating_sub(self.ready.len()).max(1);
+        let segments = self.split(value, limit);
+        self.ready.extend(segments);
+    }
+
+    /// Cut complete messages out of the pending input plus `value`, stopping
+    /// after `limit` messages or an overflow error. Whatever is left stays
+    /// pending.
+    fn split(&mut self, value: &[u8], limit: usize) -> Vec<Segment> {
+        let (next_msg, tail) = self.take_input(value);
+        let mut next_msg = next_msg.as_str();
+        let mut segments = Vec::new();
+        let mut candidate = false;
         loop {
-            match take_until::<&str, &str, Error<&str>>("<")(next_msg) {
-                Ok((res, msg)) => {
-                    self.last_msg += msg;
-                    next_msg = res;
-                    if let Ok((_, msg)) = single_parse(next_msg) {
-                        next_msg = msg.msg;
-                        if !self.last_msg.is_empty() {
-                            logs.push(self.last_msg.clone());
-                            self.last_msg.clear();
-                        }
-                        self.last_msg += &msg.header;
-                        if logs.len().ge(&self.max_size) {
-                            self.res = next_msg.as_bytes().to_vec();
-                            return Ok(logs);
-                        }
-                    } else {
-                        let first = next_msg.chars().next().unwrap_or_default();
-                        self.last_msg.push(first);
-                        next_msg = &next_msg[1..next_msg.len()];
-                    }
+            let Some(i) = memchr(b'<', next_msg.as_bytes()) else {
+                // without a '<' nothing left can start a header
+                self.append(next_msg, &mut segments);
+                next_msg = "";
+                break;
+            };
+            self.append(&next_msg[..i], &mut segments);
+            next_msg = &next_msg[i..];
+            if segments.last().is_some_and(Segment::is_error) {
+                break;
+            }
+            let possible = could_be_pri(next_msg.as_bytes());
+            let parsed = if possible {
+                parse_fields(next_msg).ok()
+            } else {
+                None
+            };
+            if let Some((_, (msg, header))) = parsed {
+                next_msg = msg.msg;
+                if !self.last_msg.is_empty() {
+                    segments.push(Segment {
+                        raw: std::mem::take(&mut self.last_msg),
+                        header: self.last_header.take(),
+                        overflow: self.last_overflow,
+                    });
                 }
-                Err(_) => {
-                    self.res = next_msg.as_bytes().to_vec();
-                    return Ok(logs);
+                self.last_overflow = None;
+                self.last_header = Some((header, msg.header.len()));
+                self.append(&msg.header, &mut segments);
+                if segments.len().ge(&limit) || segments.last().is_some_and(Segment::is_error) {
+                    break;
                 }
+            } else if possible && memchr(b'<', &next_msg.as_bytes()[1..]).is_none() {
+                // may be a header that is not complete yet, keep it pending
+                candidate = true;
+                break;
+            } else {
+                // '<' is a single byte, so this stays on a char boundary
+                self.append("<", &mut segments);
+                next_msg = &next_msg[1..];
+            }
+        }
+        self.res = [next_msg.as_bytes(), &tail].concat();
+        if self.res.len() > self.max_pending_bytes {
+            self.shrink_pending(candidate, tail.len(), &mut segments);
+        }
+        segments
+    }
+
+    /// Add text to the message being collected, enforcing `max_message_bytes`.
+    fn append(&mut self, text: &str, segments: &mut Vec<Segment>) {
+        if self.last_overflow.is_some() {
+            return;
+        }
+        self.last_msg += text;
+        if self.last_msg.len() <= self.max_message_bytes {
+            return;
+        }
+        let mut end = self.max_message_bytes;
+        while !self.last_msg.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.last_msg.truncate(end);
+        self.last_overflow = Some(self.overflow_policy);
+        match self.overflow_policy {
+            OverflowPolicy::Truncate => self.overflow_stats.truncated += 1,
+            OverflowPolicy::Drop => {
+                self.overflow_stats.dropped += 1;
+                self.last_msg.clear();
+                self.last_header = None;
+            }
+            OverflowPolicy::Error => {
+                self.overflow_stats.errors += 1;
+                segments.push(Segment {
+                    raw: std::mem::take(&mut self.last_msg),
+                    header: self.last_header.take(),
+                    overflow: self.last_overflow,
+                });
+            }
+        }
+    }
+
+    /// Bring `res` back under `max_pending_bytes`. A pending header candidate
+    /// that long is not a header, so it becomes part of the current message;
+    /// unsplit input is handled by the overflow policy.
+    fn shrink_pending(&mut self, candidate: bool, tail_len: usize, segments: &mut Vec<Segment>) {
+        if candidate {
+            let tail = self.res.split_off(self.res.len() - tail_len);
+            let text = String::from_utf8_lossy(&self.res).into_owned();
+            self.res = tail;
+            self.append(&text, segments);
+            return;
+        }
+        match self.overflow_policy {
+            OverflowPolicy::Truncate => {
+                self.overflow_stats.truncated += 1;
+                self.res.truncate(self.max_pending_bytes);
+            }
+            OverflowPolicy::Drop => {
+                self.overflow_stats.dropped += 1;
+                self.res.clear();
+            }
+            OverflowPolicy::Error => {
+                self.overflow_stats.errors += 1;
+                self.res.truncate(self.max_pending_bytes);
+                segments.push(Segment {
+                    raw: String::from_utf8_lossy(&self.res).into_owned(),
+                    header: None,
+                    overflow: Some(OverflowPolicy::Error),
+                });
+                self.res.clear();
             }
         }
     }
+
+    /// Join the pending bytes with `value` and decode them, holding back an
+    /// incomplete UTF-8 sequence at the end until the next chunk arrives.
+    fn take_input(&mut self, value: &[u8]) -> (String, Vec<u8>) {
+        let mut input = if self.last_msg.ends_with('<') {
+            self.last_msg.pop();
+            ["<".as_bytes(), &self.res, value].concat()
+        } else {
+            [self.res.as_slice(), value].concat()
+        };
+        let tail = input.split_off(incomplete_utf8_start(&input));
+        (String::from_utf8_lossy(&input).into_owned(), tail)
+    }
+}
+
+/// Index where a trailing, not yet complete UTF-8 sequence starts, or
+/// `input.len()` if the input does not end in the middle of a character.
+fn incomplete_utf8_start(input: &[u8]) -> usize {
+    let start = input.len().saturating_sub(3);
+    for i in (start..input.len()).rev() {
+        // skip continuation bytes (10xxxxxx) until the leading byte
+        if input[i] & 0xC0 == 0x80 {
+            continue;
+        }
+        return match std::str::from_utf8(&input[i..]) {
+            Err(e) if e.error_len().is_none() => i,
+            _ => input.len(),
+        };
+    }
+    input.len()
 }
 
 impl Iterator for MsgStream {
     type Item = String;
 
+    /// Oversized messages under `OverflowPolicy::Error` are skipped here;
+    /// use `next_message` to see them.
     fn next(&mut self) -> Option<Self::Item> {
-        let now = Local::now();
-        let next_msg = if self.last_msg.ends_with('<') {
-            self.last_msg.pop();
-            ["<".as_bytes().to_vec(), self.res.clone()].concat()
-        } else {
-            self.res.clone()
-        };
-        let next_msg = String::from_utf8_lossy(&next_msg);
-        let mut next_msg = next_msg.as_ref();
         loop {
-            match take_until::<&str, &str, Error<&str>>("<")(next_msg) {
-                Ok((res, msg)) => {
-                    self.last_msg += msg;
-                    next_msg = res;
-                    if let Ok((_, msg)) = single_parse(next_msg) {
-                        next_msg = msg.msg;
-                        if !self.last_msg.is_empty() {
-                            let last_msg = self.last_msg.clone();
-                            self.last_msg = msg.header;
-                            return Some(last_msg);
-                        }
-                    } else {
-                        let first = next_msg.chars().next().unwrap_or_default();
-                        self.last_msg.push(first);
-                        next_msg = &next_msg[1..next_msg.len()];
-                    }
-                }
-                Err(_) => {
-                    self.res = next_msg.as_bytes().to_vec();
-                    break;
-                }
+            let segment = self.next_segment()?;
+            if !segment.is_error() {
+                return Some(segment.raw);
             }
         }
-
-        let interval = now.signed_duration_since(self.last_parse_time);
-        if interval.ge(&Duration::milliseconds(500)) && !self.last_msg.is_empty() {
-            let msg = format!("{}{}", self.last_msg, String::from_utf8_lossy(&self.res));
-            self.clear();
-            return Some(msg);
-        }
-        None
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
+    use nom::error::Error;
 
     #[test]
     fn test_1() {
+        let clock = ManualClock::default();
         let mut parsed = MsgStream::default();
+        parsed.set_clock(Arc::new(clock.clone()));
         let value = "<11>1 2023-09-07T09:45:08.899012Z localhost <90>myprogram5424 42 9533322 - "
             .as_bytes()
             .to_vec();
@@ -144,9 +469,8 @@ mod tests {
         let msg = parsed.multi_parse::<Error<&str>>(value).unwrap();
         assert_eq!(msg.len(), 2);
 
-        parsed.last_parse_time = Local::now()
-            .checked_sub_signed(Duration::seconds(10))
-            .unwrap();
+        assert!(parsed.next().is_none());
+        clock.advance(Duration::milliseconds(500));
         let msg = parsed.next().unwrap();
         assert_eq!(msg.len(), 96);
     }
@@ -258,4 +582,265 @@ mod tests {
         assert_eq!(msg.len(), 14);
         assert_eq!(msg[0].len(), 86);
     }
+
+    #[test]
+    fn test_split_utf8() {
+        let mut parsed = MsgStream::default();
+        let value = "<11>1 2023-09-07T09:45:08.899092Z localhost myprogram5424 42 1545121 - tcp 传输 syslog<11>1 2023-09-07T09:45:08.899092Z localhost myprogram5424 42 1545122 - ".as_bytes();
+        // split inside the three bytes of '传'
+        let split = value.iter().position(|b| *b == 0xE4).unwrap() + 2;
+
+        let msg = parsed
+            .multi_parse::<Error<&str>>(value[..split].to_vec())
+            .unwrap();
+        assert_eq!(msg.len(), 0);
+        assert!(parsed.res.ends_with(&value[split - 2..split]));
+
+        let msg = parsed
+            .multi_parse::<Error<&str>>(value[split..].to_vec())
+            .unwrap();
+        assert_eq!(msg.len(), 1);
+        assert!(msg[0].ends_with("tcp 传输 syslog"));
+    }
+
+    #[test]
+    fn test_incomplete_utf8_start() {
+        let value = "传输".as_bytes();
+        assert_eq!(incomplete_utf8_start(value), value.len());
+        assert_eq!(incomplete_utf8_start(&value[..4]), 3);
+        assert_eq!(incomplete_utf8_start(&value[..5]), 3);
+        assert_eq!(incomplete_utf8_start(b"abc\xff"), 4);
+    }
+
+    #[test]
+    fn test_flush_timeout() {
+        let clock = ManualClock::default();
+        let mut parsed = MsgStream::default();
+        parsed.set_clock(Arc::new(clock.clone()));
+        parsed.set_flush_timeout(Duration::seconds(5));
+
+        let value = "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - hello world"
+            .as_bytes()
+            .to_vec();
+        let msg = parsed.multi_parse::<Error<&str>>(value).unwrap();
+        assert_eq!(msg.len(), 0);
+
+        clock.advance(Duration::seconds(4));
+        assert!(parsed.next().is_none());
+        clock.advance(Duration::seconds(1));
+        assert_eq!(
+            parsed.next().unwrap(),
+            "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - hello world"
+        );
+        assert!(parsed.next().is_none());
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut parsed = MsgStream::default();
+        parsed.set_max_size(1);
+        let value = "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - hello world<46>1 2023-09-13T11:15:47.707412Z localhost dygen 48747 186 - hello world<46>1 2023-09-13T11:15:47.719776Z localhost dygen 48747 187 - hello world"
+            .as_bytes()
+            .to_vec();
+        let msg = parsed.multi_parse::<Error<&str>>(value).unwrap();
+        assert_eq!(msg.len(), 1);
+
+        let flushed = parsed.flush();
+        assert_eq!(flushed.len(), 2);
+        assert!(flushed.iter().all(|f| f.complete));
+        assert_eq!(
+            flushed[1].msg,
+            "<46>1 2023-09-13T11:15:47.719776Z localhost dygen 48747 187 - hello world"
+        );
+        assert!(parsed.last_msg.is_empty() && parsed.res.is_empty());
+        assert!(parsed.flush().is_empty());
+    }
+
+    #[test]
+    fn test_flush_incomplete() {
+        let mut parsed = MsgStream::default();
+        let value = "<46>1 2023-09-13T11:15".as_bytes().to_vec();
+        let msg = parsed.multi_parse::<Error<&str>>(value).unwrap();
+        assert_eq!(msg.len(), 0);
+        assert_eq!(
+            parsed.flush(),
+            vec![Flushed {
+                msg: "<46>1 2023-09-13T11:15".to_string(),
+                complete: false,
+                too_large: false,
+            }]
+        );
+
+        let mut parsed = MsgStream::default();
+        let value = "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - 传输".as_bytes();
+        parsed
+            .multi_parse::<Error<&str>>(value[..value.len() - 1].to_vec())
+            .unwrap();
+        let flushed = parsed.flush();
+        assert_eq!(flushed.len(), 1);
+        assert!(!flushed[0].complete);
+    }
+
+    #[test]
+    fn test_multi_parse_messages() {
+        let mut parsed = MsgStream {
+            res: "garbage".as_bytes().to_vec(),
+            ..Default::default()
+        };
+        let value = "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - hello world<46>1 2023-09-13T11:15:47.707412Z localhost dygen 48747 186 [id@1 a=\"b\"] hello 传输<14>Oct 11 22:14:15 host su[42]: done"
+            .as_bytes()
+            .to_vec();
+        let msg = parsed.multi_parse_messages(value);
+        assert_eq!(msg.len(), 3);
+        assert_eq!(msg[0], Err(("garbage".to_string(), ErrorKind::Header)));
+        let first = msg[1].as_ref().unwrap();
+        assert_eq!(first.header.msgid.as_deref(), Some("185"));
+        assert_eq!(first.msg, "hello world");
+        let second = msg[2].as_ref().unwrap();
+        assert_eq!(
+            second.header.sd_element("id@1").unwrap().param("a"),
+            Some("b")
+        );
+        assert_eq!(second.msg, "hello 传输");
+
+        let flushed = parsed.flush_messages();
+        assert_eq!(flushed.len(), 1);
+        let last = flushed[0].as_ref().unwrap();
+        assert_eq!(last.header.appname.as_deref(), Some("su"));
+        assert_eq!(last.header.procid.as_deref(), Some("42"));
+        assert_eq!(last.msg, "done");
+    }
+
+    const TWO_MSGS: &str = "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - hello world, hello world<46>1 2023-09-13T11:15:47.707412Z localhost dygen 48747 186 - short";
+
+    #[test]
+    fn test_overflow_truncate() {
+        let mut parsed = MsgStream::default();
+        parsed.set_max_message_bytes(70);
+        let msg = parsed.multi_parse_messages(TWO_MSGS.as_bytes().to_vec());
+        assert_eq!(msg.len(), 1);
+        let first = msg[0].as_ref().unwrap();
+        assert!(first.truncated);
+        assert_eq!(first.msg, "hello wo");
+        assert_eq!(parsed.overflow_stats().truncated, 1);
+
+        let last = parsed.flush_messages();
+        assert!(!last[0].as_ref().unwrap().truncated);
+        assert_eq!(last[0].as_ref().unwrap().msg, "short");
+    }
+
+    #[test]
+    fn test_overflow_drop() {
+        let mut parsed = MsgStream::default();
+        parsed.set_max_message_bytes(70);
+        parsed.set_overflow_policy(OverflowPolicy::Drop);
+        let msg = parsed
+            .multi_parse::<Error<&str>>(TWO_MSGS.as_bytes().to_vec())
+            .unwrap();
+        assert!(msg.is_empty());
+        let flushed = parsed.flush();
+        assert_eq!(flushed.len(), 1);
+        assert!(flushed[0].msg.ends_with("short"));
+        assert_eq!(
+            parsed.overflow_stats(),
+            OverflowStats {
+                dropped: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_overflow_error() {
+        let mut parsed = MsgStream::default();
+        parsed.set_max_message_bytes(70);
+        parsed.set_overflow_policy(OverflowPolicy::Error);
+        let value = format!("{}{}", TWO_MSGS, TWO_MSGS);
+        let msg = parsed.multi_parse::<Error<&str>>(value.as_bytes().to_vec());
+        assert!(msg.is_err());
+        // the second oversized message is reported as soon as it is cut,
+        // the "short" one before it comes with the next call
+        let msg = parsed.multi_parse::<Error<&str>>(vec![]);
+        assert!(msg.is_err());
+        let msg = parsed.multi_parse::<Error<&str>>(vec![]).unwrap();
+        assert_eq!(msg.len(), 1);
+        assert!(msg[0].ends_with("short"));
+
+        let msg = parsed.flush_messages();
+        assert_eq!(msg.len(), 1);
+        assert_eq!(msg[0].as_ref().unwrap().msg, "short");
+        assert_eq!(parsed.overflow_stats().errors, 2);
+
+        // still pending when the stream ends
+        let mut parsed = MsgStream::default();
+        parsed.set_max_message_bytes(70);
+        parsed.set_overflow_policy(OverflowPolicy::Error);
+        parsed.set_max_size(1);
+        let (big, short) = TWO_MSGS.split_at(TWO_MSGS.rfind("<46>").unwrap());
+        let msg = parsed.multi_parse_messages(format!("{}{}", short, big).into_bytes());
+        assert_eq!(msg.len(), 1);
+        assert_eq!(msg[0].as_ref().unwrap().msg, "short");
+        let flushed = parsed.flush();
+        assert_eq!(flushed.len(), 1);
+        assert!(flushed[0].too_large);
+        assert!(!flushed[0].complete);
+        assert_eq!(flushed[0].msg.len(), 70);
+    }
+
+    #[test]
+    fn test_overflow_header() {
+        let header = "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 [id@1 a=\"bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb\"] ";
+        let value = format!("{}hi<14>Oct 11 22:14:15 host su: done", header);
+
+        let mut parsed = MsgStream::default();
+        parsed.set_max_message_bytes(64);
+        let msg = parsed.multi_parse_messages(value.clone().into_bytes());
+        assert_eq!(msg.len(), 1);
+        let first = msg[0].as_ref().unwrap();
+        assert!(first.truncated);
+        assert_eq!(first.msg, "");
+        assert_eq!(parsed.overflow_stats().truncated, 1);
+
+        let mut parsed = MsgStream::default();
+        parsed.set_max_message_bytes(64);
+        parsed.set_overflow_policy(OverflowPolicy::Error);
+        let msg = parsed.multi_parse::<Error<&str>>(value.into_bytes());
+        assert!(msg.is_err());
+        let flushed = parsed.flush();
+        assert_eq!(flushed.len(), 1);
+        assert!(flushed[0].msg.ends_with("done"));
+    }
+
+    #[test]
+    fn test_overflow_without_header() {
+        let mut parsed = MsgStream::default();
+        parsed.set_max_message_bytes(64);
+        parsed.set_max_pending_bytes(64);
+        for _ in 0..100 {
+            let msg = parsed
+                .multi_parse::<Error<&str>>(
+                    "no header here, <not a pri either ".as_bytes().to_vec(),
+                )
+                .unwrap();
+            assert!(msg.is_empty());
+            assert!(parsed.last_msg.len() <= 64);
+            assert!(parsed.res.len() <= 64);
+        }
+        assert_eq!(parsed.overflow_stats().truncated, 1);
+    }
+
+    #[test]
+    fn test_multi_byte_around_candidates() {
+        let mut parsed = MsgStream::default();
+        let value = "传<传 a<b <1x <999> 输<<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - 传<"
+            .as_bytes()
+            .to_vec();
+        let msg = parsed.multi_parse::<Error<&str>>(value).unwrap();
+        assert_eq!(msg, vec!["传<传 a<b <1x <999> 输<".to_string()]);
+        let flushed = parsed.flush();
+        assert_eq!(
+            flushed[0].msg,
+            "<46>1 2023-09-13T11:15:47.697131Z localhost dygen 48747 185 - 传<"
+        );
+    }
 }
@@ -0,0 +1,546 @@
+//! rsyslog style output templates.
+//!
+//! ```
+//! use syslog_parse::message::Message;
+//! use syslog_parse::template::Template;
+//!
+//! let template: Template =
+//!     r"%TIMESTAMP:::date-rfc3339% %HOSTNAME% %APP-NAME%[%PROCID%]: %msg%\n"
+//!         .parse()
+//!         .unwrap();
+//! let msg = Message::parse("<38>1 2024-03-01T12:00:00Z web1 sshd 42 - - accepted").unwrap();
+//! assert_eq!(template.render(&msg), "2024-03-01T12:00:00Z web1 sshd[42]: accepted\n");
+//! ```
+//!
+//! A property is `%name%` or `%name:from:to:options%`. `from` and `to`
+//! pick characters, counting from 1, `$` being the last one. Options are
+//! a `,` separated list of:
+//!
+//! - `date-rfc3339`, `date-rfc3164`, `date-unixtimestamp`, `date-mysql`,
+//!   `date-year`, `date-month`, `date-day`, `date-hour`, `date-minute`,
+//!   `date-second`: reformat a timestamp, RFC3164 being the default for
+//!   `timestamp`
+//! - `uppercase`, `lowercase`
+//! - `drop-last-lf`, `escape-cc`, `sp-if-no-1st-sp`
+//! - `json`, `csv`: escape for a JSON string or a CSV field
+//! - `default=text`: the value when the property is missing
+//!
+//! Property names are case-insensitive: `msg`, `hostname`
+//! (`source`), `syslogtag`, `programname`, `app-name`, `procid`, `msgid`,
+//! `pri`, `pri-text`, `syslogfacility`, `syslogfacility-text`,
+//! `syslogseverity` (`syslogpriority`), `syslogseverity-text`
+//! (`syslogpriority-text`), `timestamp` (`timereported`),
+//! `protocol-version`, `structured-data`, and `sd!id` or `sd!id!param`
+//! for structured data. `rawmsg` is not supported, as the bytes received
+//! are not kept. Missing RFC5424 header fields render as `-`,
+//! other missing properties as nothing. In the text around properties,
+//! `\n`, `\t`, `\r`, `\\` and `\%` are escapes.
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::{ExprError, ExprErrorKind, UnknownName};
+use crate::field::Field;
+use crate::message::Message;
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// rsyslog's `RSYSLOG_TraditionalFileFormat`.
+pub const TRADITIONAL_FILE_FORMAT: &str =
+    r"%TIMESTAMP% %HOSTNAME% %syslogtag%%msg:::sp-if-no-1st-sp%%msg:::drop-last-lf%\n";
+/// rsyslog's `RSYSLOG_FileFormat`, with RFC3339 timestamps.
+pub const FILE_FORMAT: &str = r"%TIMESTAMP:::date-rfc3339% %HOSTNAME% %syslogtag%%msg:::sp-if-no-1st-sp%%msg:::drop-last-lf%\n";
+/// rsyslog's `RSYSLOG_SyslogProtocol23Format`, i.e. RFC5424.
+pub const SYSLOG_PROTOCOL_FORMAT: &str = r"<%PRI%>1 %TIMESTAMP:::date-rfc3339% %HOSTNAME% %APP-NAME% %PROCID% %MSGID% %STRUCTURED-DATA% %msg%\n";
+
+/// A compiled template.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+    clock: Arc<dyn Clock>,
+}
+
+impl PartialEq for Template {
+    fn eq(&self, other: &Self) -> bool {
+        self.parts == other.parts
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Property(Property),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Property {
+    value: Value,
+    from: usize,
+    to: Option<usize>,
+    date: Option<Date>,
+    case: Option<Case>,
+    drop_last_lf: bool,
+    escape_cc: bool,
+    sp_if_no_1st_sp: bool,
+    escape: Option<Escape>,
+    default: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Field(Field),
+    SyslogTag,
+    ProgramName,
+    PriText,
+    FacilityCode,
+    SeverityCode,
+    Sd(String, Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Date {
+    Rfc3339,
+    Rfc3164,
+    Unix,
+    Mysql,
+    /// A `strftime` format for one part of the date.
+    Part(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Case {
+    Upper,
+    Lower,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escape {
+    Json,
+    Csv,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template, ExprError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.char_indices();
+        while let Some((at, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => text.push('\n'),
+                    Some((_, 't')) => text.push('\t'),
+                    Some((_, 'r')) => text.push('\r'),
+                    Some((_, e @ ('\\' | '%'))) => text.push(e),
+                    Some((_, e)) => {
+                        text.push('\\');
+                        text.push(e);
+                    }
+                    None => text.push('\\'),
+                },
+                '%' => {
+                    let start = at + 1;
+                    let Some(len) = template[start..].find('%') else {
+                        return Err(ExprError::new(
+                            template.len(),
+                            ExprErrorKind::Expected {
+                                expected: "%",
+                                found: "结尾".to_string(),
+                            },
+                        ));
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    let property = Property::parse(&template[start..start + len], start)?;
+                    parts.push(Part::Property(property));
+                    for _ in template[start..=start + len].chars() {
+                        chars.next();
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template {
+            parts,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Clock for the year of RFC3164 timestamps without one: they get the
+    /// year closest to its time.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn render(&self, msg: &Message) -> String {
+        let mut out = String::new();
+        self.render_into(msg, &mut out);
+        out
+    }
+
+    /// `render`, appending to `out`.
+    pub fn render_into(&self, msg: &Message, out: &mut String) {
+        let now = self.clock.now().fixed_offset();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Property(property) => out.push_str(&property.render(msg, now)),
+            }
+        }
+    }
+}
+
+impl FromStr for Template {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, ExprError> {
+        Template::parse(s)
+    }
+}
+
+impl Property {
+    /// `spec` is the text between the `%`s, starting at byte `at`.
+    fn parse(spec: &str, at: usize) -> Result<Property, ExprError> {
+        let mut fields = spec.splitn(4, ':');
+        let name = fields.next().unwrap_or_default();
+        let value = Value::parse(name).map_err(|e| ExprError::new(at, e.into()))?;
+        let mut property = Property {
+            value,
+            from: 1,
+            to: None,
+            date: None,
+            case: None,
+            drop_last_lf: false,
+            escape_cc: false,
+            sp_if_no_1st_sp: false,
+            escape: None,
+            default: None,
+        };
+        let mut offset = at + name.len() + 1;
+        let number = |text: &str, offset: usize| {
+            text.parse::<usize>()
+                .map_err(|_| ExprError::new(offset, ExprErrorKind::Number(text.to_string())))
+        };
+        if let Some(from) = fields.next() {
+            if !from.is_empty() {
+                property.from = number(from, offset)?.max(1);
+            }
+            offset += from.len() + 1;
+        }
+        if let Some(to) = fields.next() {
+            if !to.is_empty() && to != "$" {
+                property.to = Some(number(to, offset)?);
+            }
+            offset += to.len() + 1;
+        }
+        let Some(options) = fields.next() else {
+            return Ok(property);
+        };
+        if let Some(default) = options.find("default=") {
+            property.default = Some(options[default + "default=".len()..].to_string());
+        }
+        let options = match options.find("default=") {
+            Some(default) => &options[..default],
+            None => options,
+        };
+        for option in options.split(',') {
+            match option.trim().to_ascii_lowercase().as_str() {
+                "" => {}
+                "date-rfc3339" => property.date = Some(Date::Rfc3339),
+                "date-rfc3164" => property.date = Some(Date::Rfc3164),
+                "date-unixtimestamp" => property.date = Some(Date::Unix),
+                "date-mysql" => property.date = Some(Date::Mysql),
+                "date-year" => property.date = Some(Date::Part("%Y")),
+                "date-month" => property.date = Some(Date::Part("%m")),
+                "date-day" => property.date = Some(Date::Part("%d")),
+                "date-hour" => property.date = Some(Date::Part("%H")),
+                "date-minute" => property.date = Some(Date::Part("%M")),
+                "date-second" => property.date = Some(Date::Part("%S")),
+                "uppercase" => property.case = Some(Case::Upper),
+                "lowercase" => property.case = Some(Case::Lower),
+                "drop-last-lf" => property.drop_last_lf = true,
+                "escape-cc" => property.escape_cc = true,
+                "sp-if-no-1st-sp" => property.sp_if_no_1st_sp = true,
+                "json" => property.escape = Some(Escape::Json),
+                "csv" => property.escape = Some(Escape::Csv),
+                _ => {
+                    return Err(ExprError::new(
+                        offset,
+                        UnknownName::new("option", option).into(),
+                    ))
+                }
+            }
+            offset += option.len() + 1;
+        }
+        Ok(property)
+    }
+
+    /// As in rsyslog, a timestamp without a date option is RFC3164.
+    fn date(&self) -> Option<Date> {
+        match self.value {
+            Value::Field(Field::Timestamp) => self.date.or(Some(Date::Rfc3164)),
+            _ => self.date,
+        }
+    }
+
+    fn render(&self, msg: &Message, now: DateTime<FixedOffset>) -> String {
+        let mut value = match (self.value.get(msg), &self.default) {
+            (Some(value), _) => value.into_owned(),
+            (None, Some(default)) => default.clone(),
+            (None, None) => self.value.missing().to_string(),
+        };
+        if let Some(date) = self.date() {
+            if let Some(time) = msg.timestamp_near(now) {
+                value = match date {
+                    Date::Rfc3339 => time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                    Date::Rfc3164 => time.format("%b %e %H:%M:%S").to_string(),
+                    Date::Unix => time.timestamp().to_string(),
+                    Date::Mysql => time.format("%Y%m%d%H%M%S").to_string(),
+                    Date::Part(format) => time.format(format).to_string(),
+                };
+            }
+        }
+        if self.from > 1 || self.to.is_some() {
+            let len = self
+                .to
+                .map_or(usize::MAX, |to| (to + 1).saturating_sub(self.from));
+            value = value.chars().skip(self.from - 1).take(len).collect();
+        }
+        match self.case {
+            Some(Case::Upper) => value = value.to_uppercase(),
+            Some(Case::Lower) => value = value.to_lowercase(),
+            None => {}
+        }
+        if self.drop_last_lf && value.ends_with('\n') {
+            value.pop();
+        }
+        if self.escape_cc && value.contains(char::is_control) {
+            value = value
+                .chars()
+                .map(|c| match c {
+                    c if c.is_control() => format!("#{:03o}", c as u32),
+                    c => c.to_string(),
+                })
+                .collect();
+        }
+        if self.sp_if_no_1st_sp {
+            // only the space, not the value
+            return match value.starts_with(' ') {
+                true => String::new(),
+                false => " ".to_string(),
+            };
+        }
+        match self.escape {
+            Some(Escape::Json) => {
+                let quoted = serde_json::to_string(&value).unwrap();
+                quoted[1..quoted.len() - 1].to_string()
+            }
+            Some(Escape::Csv) => format!("\"{}\"", value.replace('"', "\"\"")),
+            None => value,
+        }
+    }
+}
+
+impl Value {
+    fn parse(name: &str) -> Result<Value, UnknownName> {
+        let lower = name.to_ascii_lowercase();
+        if let Some(path) = lower.strip_prefix("sd!") {
+            // keep the case of SD-IDs and names
+            let path = &name[3..3 + path.len()];
+            return Ok(match path.split_once('!') {
+                Some((id, param)) => Value::Sd(id.to_string(), Some(param.to_string())),
+                None => Value::Sd(path.to_string(), None),
+            });
+        }
+        Ok(match lower.as_str() {
+            "msg" => Value::Field(Field::Msg),
+            "hostname" | "source" => Value::Field(Field::Hostname),
+            "syslogtag" => Value::SyslogTag,
+            "programname" => Value::ProgramName,
+            "app-name" => Value::Field(Field::Appname),
+            "procid" => Value::Field(Field::Procid),
+            "msgid" => Value::Field(Field::Msgid),
+            "pri" => Value::Field(Field::Pri),
+            "pri-text" => Value::PriText,
+            "syslogfacility" => Value::FacilityCode,
+            "syslogfacility-text" => Value::Field(Field::Facility),
+            "syslogseverity" | "syslogpriority" => Value::SeverityCode,
+            "syslogseverity-text" | "syslogpriority-text" => Value::Field(Field::Severity),
+            "timestamp" | "timereported" => Value::Field(Field::Timestamp),
+            "protocol-version" => Value::Field(Field::Version),
+            "structured-data" => Value::Field(Field::Sd),
+            _ => return Err(UnknownName::new("property", name)),
+        })
+    }
+
+    fn get<'a>(&self, msg: &'a Message) -> Option<Cow<'a, str>> {
+        let h = &msg.header;
+        match self {
+            Value::Field(field) => field.get(msg),
+            Value::SyslogTag => h.appname.as_ref().map(|app| match &h.procid {
+                Some(procid) => format!("{}[{}]:", app, procid).into(),
+                None => format!("{}:", app).into(),
+            }),
+            Value::ProgramName => h.appname.as_deref().map(Cow::Borrowed),
+            Value::PriText => Some(format!("{}.{}", h.facility, h.severity).into()),
+            Value::FacilityCode => Some(h.facility.code().to_string().into()),
+            Value::SeverityCode => Some(h.severity.code().to_string().into()),
+            Value::Sd(id, None) => h.sd_element(id).map(|e| e.to_string().into()),
+            Value::Sd(id, Some(param)) => h
+                .sd_element(id)
+                .and_then(|e| e.param(param))
+                .map(Cow::Borrowed),
+        }
+    }
+
+    /// What a missing value renders as: the NILVALUE for RFC5424 header
+    /// fields.
+    fn missing(&self) -> &'static str {
+        match self {
+            Value::Field(
+                Field::Hostname | Field::Appname | Field::Procid | Field::Msgid | Field::Sd,
+            ) => "-",
+            _ => "",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use chrono::{Local, TimeZone};
+
+    const SSHD: &str = r#"<38>1 2024-03-01T12:00:00.25+02:00 web1 sshd 42 - [origin ip="10.0.0.7"] Accepted "key"
+"#;
+    const CRON: &str = "<78>Mar  1 12:00:00 cron: job done";
+
+    fn render(template: &str, msg: &str) -> String {
+        let template = Template::parse(template).unwrap_or_else(|e| panic!("{}: {}", template, e));
+        template.render(&Message::parse(msg).unwrap())
+    }
+
+    #[test]
+    fn test_properties() {
+        assert_eq!(
+            render(
+                r"%TIMESTAMP:::date-rfc3339% %HOSTNAME% %APP-NAME%[%PROCID%]: %msg:::drop-last-lf%\n",
+                SSHD
+            ),
+            "2024-03-01T12:00:00.250+02:00 web1 sshd[42]: Accepted \"key\"\n"
+        );
+        assert_eq!(
+            render(
+                "%pri% %pri-text% %syslogfacility% %syslogseverity-text%",
+                SSHD
+            ),
+            "38 auth.info 4 info"
+        );
+        assert_eq!(
+            render("%sd!origin!ip% %sd!origin% %sd!meta!x%|", SSHD),
+            "10.0.0.7 [origin ip=\"10.0.0.7\"] |"
+        );
+        assert_eq!(
+            render("%syslogtag%%msg:::sp-if-no-1st-sp%%msg%", CRON),
+            "cron: job done"
+        );
+        // NILVALUEs for missing RFC5424 header fields
+        assert_eq!(
+            render(
+                "%HOSTNAME% %PROCID% %MSGID% %structured-data% %protocol-version%|",
+                CRON
+            ),
+            "- - - - |"
+        );
+        assert_eq!(
+            render(
+                "%hostname:::default=unknown% %msgid:::default=none,x%",
+                CRON
+            ),
+            "unknown none,x"
+        );
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert_eq!(render("%msg:1:8%|%msg:10:$%", CRON), "job done|");
+        assert_eq!(render("%msg:5:$%|%msg:3:2%|", CRON), "done||");
+        assert_eq!(render("%programname:::uppercase%", CRON), "CRON");
+        assert_eq!(render("%HOSTNAME:1:3:uppercase%", SSHD), "WEB");
+        assert_eq!(render("%msg:::json%", SSHD), r#"Accepted \"key\"\n"#);
+        assert_eq!(
+            render("%msg:::csv,drop-last-lf%", SSHD),
+            r#""Accepted ""key""""#
+        );
+        assert_eq!(render("%msg:::escape-cc%", SSHD), "Accepted \"key\"#012");
+        assert_eq!(
+            render(
+                "%timestamp:::date-unixtimestamp% %timestamp:::date-mysql%",
+                SSHD
+            ),
+            "1709287200 20240301120000"
+        );
+        assert_eq!(
+            render("%timestamp:::date-year%-%timestamp:::date-hour%", SSHD),
+            "2024-12"
+        );
+        assert_eq!(render(r"100\% \\ \x", CRON), r"100% \ \x");
+    }
+
+    #[test]
+    fn test_year() {
+        let mut template = Template::parse("%timestamp:::date-mysql%").unwrap();
+        let now = Local.with_ymd_and_hms(2025, 1, 1, 0, 10, 0).unwrap();
+        template.set_clock(Arc::new(ManualClock::new(now)));
+        let msg = Message::parse("<13>Dec 31 23:59:00 host app: hi").unwrap();
+        assert_eq!(template.render(&msg), "20241231235900");
+        let msg = Message::parse("<13>Jan  1 00:05:00 host app: hi").unwrap();
+        assert_eq!(template.render(&msg), "20250101000500");
+    }
+
+    #[test]
+    fn test_predefined() {
+        let msg = "<13>1 2024-03-01T12:00:00Z host app 7 ID1 - hi";
+        assert_eq!(
+            render(SYSLOG_PROTOCOL_FORMAT, msg),
+            "<13>1 2024-03-01T12:00:00Z host app 7 ID1 - hi\n"
+        );
+        assert_eq!(
+            render(FILE_FORMAT, msg),
+            "2024-03-01T12:00:00Z host app[7]: hi\n"
+        );
+        assert_eq!(
+            render(TRADITIONAL_FILE_FORMAT, msg),
+            "Mar  1 12:00:00 host app[7]: hi\n"
+        );
+        let msg = "<13>Mar 11 12:00:00 host app: hi";
+        assert_eq!(
+            render(TRADITIONAL_FILE_FORMAT, msg),
+            "Mar 11 12:00:00 host app: hi\n"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = Template::parse("%msg% %nope%").unwrap_err();
+        assert_eq!(error.position, 7);
+        assert_eq!(
+            error.kind,
+            ExprErrorKind::UnknownName(UnknownName::new("property", "nope"))
+        );
+        let error = Template::parse("%rawmsg%").unwrap_err();
+        assert_eq!(
+            error.kind,
+            ExprErrorKind::UnknownName(UnknownName::new("property", "rawmsg"))
+        );
+        let error = Template::parse("a %msg").unwrap_err();
+        assert_eq!(error.position, 6);
+        let error = Template::parse("%msg:x%").unwrap_err();
+        assert_eq!(error.position, 5);
+        let error = Template::parse("%msg:::json,loud%").unwrap_err();
+        assert_eq!(error.position, 12);
+    }
+}
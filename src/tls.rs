@@ -0,0 +1,104 @@
+//! TLS settings shared by the RFC5425 receiver and sender.
+
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use std::sync::Arc;
+
+pub use rustls;
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// Server settings. With `client_roots`, clients must present a
+/// certificate issued by one of them (mutual TLS).
+pub fn server_config(
+    cert_chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    client_roots: Option<RootCertStore>,
+) -> Result<Arc<ServerConfig>, rustls::Error> {
+    let builder =
+        ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+    let builder = match client_roots {
+        Some(roots) => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider())
+                .build()
+                .map_err(|e| rustls::Error::General(e.to_string()))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    builder.with_single_cert(cert_chain, key).map(Arc::new)
+}
+
+/// Client settings, trusting `roots`, with an optional client certificate
+/// for mutual TLS.
+pub fn client_config(
+    roots: RootCertStore,
+    client_cert: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+) -> Result<Arc<ClientConfig>, rustls::Error> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots);
+    match client_cert {
+        Some((cert_chain, key)) => builder.with_client_auth_cert(cert_chain, key),
+        None => Ok(builder.with_no_client_auth()),
+    }
+    .map(Arc::new)
+}
+
+/// The subject of a DER certificate, as `CN=host, O=org`.
+pub(crate) fn subject(cert: &CertificateDer<'_>) -> Option<String> {
+    x509_parser::parse_x509_certificate(cert)
+        .ok()
+        .map(|(_, cert)| cert.subject().to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, DnType, IsCa, KeyPair};
+    use rustls::pki_types::PrivatePkcs8KeyDer;
+
+    pub struct Pki {
+        pub roots: RootCertStore,
+        pub server: (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>),
+        pub client: (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>),
+    }
+
+    /// A CA, a certificate for `localhost` and one for `CN=client1`.
+    pub fn pki() -> Pki {
+        let mut params = CertificateParams::new(vec![]).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "test ca");
+        let ca = CertifiedIssuer::self_signed(params, KeyPair::generate().unwrap()).unwrap();
+
+        let issue = |name: &str| {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(vec![name.to_string()]).unwrap();
+            params.distinguished_name.push(DnType::CommonName, name);
+            let cert = params.signed_by(&key, &ca).unwrap();
+            let key = PrivatePkcs8KeyDer::from(key.serialize_der());
+            (vec![cert.der().clone()], key.into())
+        };
+
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.der().clone()).unwrap();
+        Pki {
+            roots,
+            server: issue("localhost"),
+            client: issue("client1"),
+        }
+    }
+
+    #[test]
+    fn test_subject() {
+        let pki = pki();
+        assert_eq!(subject(&pki.client.0[0]).as_deref(), Some("CN=client1"));
+        assert_eq!(subject(&CertificateDer::from(vec![1, 2, 3])), None);
+    }
+}